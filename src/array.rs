@@ -0,0 +1,169 @@
+use crate::{CompileError, Result};
+use cranelift::{
+	codegen::ir::{InstructionData, Opcode, ValueDef},
+	prelude::*,
+};
+
+/// The trap raised by default when an array or slice index is out of bounds.
+/// Use `Array::trap` to raise a different code.
+pub const BOUNDS_CHECK_TRAP: TrapCode = TrapCode::User(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArrayLen {
+	/// A length known at compile time, as for fixed-size arrays.
+	Fixed(u64),
+	/// A length only known at run time, as for `(ptr, len)` slices.
+	Dynamic(Value),
+}
+
+/// A contiguous run of equally sized elements whose accesses are checked
+/// against its length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Array {
+	pub ptr: Value,
+	pub len: ArrayLen,
+	pub elem_size: u32,
+	/// The trap raised when an index is out of bounds.
+	pub trap: TrapCode,
+}
+
+impl Array {
+	pub fn fixed(ptr: Value, len: u64, elem_size: u32) -> Array {
+		Array {
+			ptr,
+			len: ArrayLen::Fixed(len),
+			elem_size,
+			trap: BOUNDS_CHECK_TRAP,
+		}
+	}
+
+	pub fn slice(ptr: Value, len: Value, elem_size: u32) -> Array {
+		Array {
+			ptr,
+			len: ArrayLen::Dynamic(len),
+			elem_size,
+			trap: BOUNDS_CHECK_TRAP,
+		}
+	}
+
+	/// Raises `trap` instead of `BOUNDS_CHECK_TRAP` on out of bounds
+	/// accesses.
+	pub fn trap(mut self, trap: TrapCode) -> Array {
+		self.trap = trap;
+		self
+	}
+
+	/// Returns the address of the element at `index`, trapping with the
+	/// array's trap code if it is out of bounds. The check is left out
+	/// when `index` is a constant known to be in range.
+	pub fn elem_addr(&self, index: Value, f: &mut FunctionBuilder) -> Value {
+		let ptr_type = f.func.dfg.value_type(self.ptr);
+
+		if let (Some(index), ArrayLen::Fixed(len)) =
+			(const_index(index, f), self.len)
+		{
+			if index < len {
+				let offset = index * self.elem_size as u64;
+				return f.ins().iadd_imm(self.ptr, offset as i64);
+			}
+		}
+
+		// Compare at the widest of the index, length and pointer types so
+		// that high bits dropped when narrowing the index can't bring it in
+		// bounds.
+		let mut cmp_type = wider(f.func.dfg.value_type(index), ptr_type);
+		if let ArrayLen::Dynamic(len) = self.len {
+			cmp_type = wider(cmp_type, f.func.dfg.value_type(len));
+		}
+
+		let wide_index = cast_to(index, cmp_type, f);
+		let in_bounds = match self.len {
+			ArrayLen::Fixed(len) => f.ins().icmp_imm(
+				IntCC::UnsignedLessThan,
+				wide_index,
+				len as i64,
+			),
+			ArrayLen::Dynamic(len) => {
+				let len = cast_to(len, cmp_type, f);
+				f.ins().icmp(IntCC::UnsignedLessThan, wide_index, len)
+			}
+		};
+		f.ins().trapz(in_bounds, self.trap);
+
+		let index = cast_to(wide_index, ptr_type, f);
+		let offset = f.ins().imul_imm(index, self.elem_size as i64);
+		f.ins().iadd(self.ptr, offset)
+	}
+
+	pub fn load(
+		&self,
+		elem_type: Type,
+		index: Value,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		self.check_elem_type(elem_type)?;
+
+		let addr = self.elem_addr(index, f);
+		Ok(f.ins().load(elem_type, MemFlags::new(), addr, 0))
+	}
+
+	pub fn store(
+		&self,
+		index: Value,
+		val: Value,
+		f: &mut FunctionBuilder,
+	) -> Result<()> {
+		self.check_elem_type(f.func.dfg.value_type(val))?;
+
+		let addr = self.elem_addr(index, f);
+		f.ins().store(MemFlags::new(), val, addr, 0);
+
+		Ok(())
+	}
+
+	fn check_elem_type(&self, elem_type: Type) -> Result<()> {
+		if elem_type.bytes() != self.elem_size {
+			return Err(CompileError::ElementSizeMismatch {
+				expected: self.elem_size,
+				found: elem_type,
+			});
+		}
+
+		Ok(())
+	}
+}
+
+fn const_index(index: Value, f: &FunctionBuilder) -> Option<u64> {
+	match f.func.dfg.value_def(index) {
+		ValueDef::Result(inst, _) => match f.func.dfg[inst] {
+			InstructionData::UnaryImm {
+				opcode: Opcode::Iconst,
+				imm,
+			} => Some(i64::from(imm) as u64),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+fn wider(a: Type, b: Type) -> Type {
+	if a.bits() >= b.bits() {
+		a
+	} else {
+		b
+	}
+}
+
+/// Zero-extends or truncates an integer to `ty`. Indices are treated as
+/// unsigned, so negative ones always fail the bounds check.
+fn cast_to(val: Value, ty: Type, f: &mut FunctionBuilder) -> Value {
+	let val_type = f.func.dfg.value_type(val);
+
+	if val_type.bits() < ty.bits() {
+		f.ins().uextend(ty, val)
+	} else if val_type.bits() > ty.bits() {
+		f.ins().ireduce(ty, val)
+	} else {
+		val
+	}
+}
@@ -0,0 +1,52 @@
+#[cfg(any(feature = "jit", feature = "object"))]
+use crate::Result;
+use crate::{CompileError, Compiler};
+use cranelift_module::Module;
+use std::{error::Error, fmt};
+
+/// A function whose definition failed while the compiler was in batch mode.
+#[derive(Debug)]
+pub struct FuncError {
+	pub func: String,
+	pub error: CompileError,
+}
+
+impl Error for FuncError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		Some(&self.error)
+	}
+}
+
+impl fmt::Display for FuncError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "in function {}: {}", self.func, self.error)
+	}
+}
+
+impl<M: Module> Compiler<M> {
+	/// Enables or disables batch mode. In batch mode `define_func` and
+	/// `compile_func` record a failing function and carry on instead of
+	/// returning the error, and every recorded failure is reported together
+	/// when the module is finished. Errors from declaring a function are
+	/// still returned directly.
+	pub fn set_batch(&mut self, enabled: bool) {
+		self.batch = enabled;
+	}
+
+	/// The failures recorded in batch mode so far, in definition order.
+	pub fn failures(&self) -> &[FuncError] {
+		&self.failures
+	}
+
+	/// Fails with every failure recorded in batch mode, leaving none behind.
+	#[cfg(any(feature = "jit", feature = "object"))]
+	pub(crate) fn take_failures(&mut self) -> Result<()> {
+		if !self.failures.is_empty() {
+			return Err(CompileError::Functions(std::mem::take(
+				&mut self.failures,
+			)));
+		}
+
+		Ok(())
+	}
+}
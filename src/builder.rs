@@ -0,0 +1,108 @@
+#[cfg(any(feature = "jit", feature = "object"))]
+use crate::Result;
+use cranelift::prelude::settings::OptLevel;
+#[cfg(any(feature = "jit", feature = "object"))]
+use cranelift::prelude::{
+	isa::{Builder as IsaBuilder, TargetIsa},
+	settings, Configurable,
+};
+
+/// Collects codegen settings and target features before constructing a
+/// module and its `Compiler`.
+#[derive(Clone, Debug, Default)]
+pub struct CompilerBuilder {
+	pub(crate) target: Option<String>,
+	flags: Vec<(String, String)>,
+	features: Vec<String>,
+	/// Host addresses of JIT symbols, kept as integers so that the builder
+	/// stays `Send` and `Sync`.
+	#[cfg(feature = "jit")]
+	pub(crate) symbols: Vec<(String, usize)>,
+}
+
+impl CompilerBuilder {
+	pub fn new() -> CompilerBuilder {
+		CompilerBuilder::default()
+	}
+
+	/// Sets the target triple to compile for instead of the host.
+	pub fn target(mut self, triple: &str) -> CompilerBuilder {
+		self.target = Some(triple.to_owned());
+		self
+	}
+
+	/// Sets any shared Cranelift flag by name. Invalid names or values are
+	/// reported when the compiler is built.
+	pub fn flag(mut self, name: &str, value: &str) -> CompilerBuilder {
+		self.flags.push((name.to_owned(), value.to_owned()));
+		self
+	}
+
+	pub fn opt_level(self, level: OptLevel) -> CompilerBuilder {
+		let level = match level {
+			OptLevel::None => "none",
+			OptLevel::Speed => "speed",
+			OptLevel::SpeedAndSize => "speed_and_size",
+		};
+
+		self.flag("opt_level", level)
+	}
+
+	pub fn enable_verifier(self, enabled: bool) -> CompilerBuilder {
+		self.flag("enable_verifier", bool_str(enabled))
+	}
+
+	pub fn is_pic(self, enabled: bool) -> CompilerBuilder {
+		self.flag("is_pic", bool_str(enabled))
+	}
+
+	pub fn enable_probestack(self, enabled: bool) -> CompilerBuilder {
+		self.flag("enable_probestack", bool_str(enabled))
+	}
+
+	/// Enables or disables the Spectre mitigations for heap and table
+	/// accesses.
+	pub fn spectre_mitigations(self, enabled: bool) -> CompilerBuilder {
+		self.flag("enable_heap_access_spectre_mitigation", bool_str(enabled))
+			.flag("enable_table_access_spectre_mitigation", bool_str(enabled))
+	}
+
+	/// Enables an ISA-specific feature such as `has_avx2`.
+	pub fn target_feature(mut self, name: &str) -> CompilerBuilder {
+		self.features.push(name.to_owned());
+		self
+	}
+
+	/// Finishes `isa_builder` with the collected settings, applying
+	/// `defaults` first so that anything set explicitly overrides them.
+	#[cfg(any(feature = "jit", feature = "object"))]
+	pub(crate) fn finish_isa(
+		&self,
+		mut isa_builder: IsaBuilder,
+		defaults: &[(&str, &str)],
+	) -> Result<Box<dyn TargetIsa>> {
+		let mut flag_builder = settings::builder();
+
+		for (name, value) in defaults {
+			flag_builder.set(name, value)?;
+		}
+
+		for (name, value) in &self.flags {
+			flag_builder.set(name, value)?;
+		}
+
+		for feature in &self.features {
+			isa_builder.enable(feature)?;
+		}
+
+		Ok(isa_builder.finish(settings::Flags::new(flag_builder))?)
+	}
+}
+
+fn bool_str(value: bool) -> &'static str {
+	if value {
+		"true"
+	} else {
+		"false"
+	}
+}
@@ -0,0 +1,304 @@
+use crate::{CompileError, Compiler, Result};
+use cranelift::{frontend::Switch, prelude::*};
+use cranelift_module::Module;
+use std::collections::HashSet;
+
+/// The body of a single `match_int` arm.
+pub type MatchArm<'a, M> = Box<
+	dyn FnOnce(&mut Compiler<M>, &mut FunctionBuilder) -> Result<Vec<Value>>
+		+ 'a,
+>;
+
+pub(crate) struct LoopCtx {
+	header: Block,
+	exit: Block,
+}
+
+/// Whether code emitted so far in the current block can still reach the end
+/// of it. Blocks ended by a terminator or by `break_loop`/`continue_loop`
+/// don't.
+fn falls_through(f: &FunctionBuilder) -> bool {
+	!(f.is_filled() || (f.is_unreachable() && f.is_pristine()))
+}
+
+impl<M: Module> Compiler<M> {
+	/// Runs `then` if `cond` is non-zero and `els` otherwise, returning the
+	/// values of whichever branch ran. Both branches must produce values of
+	/// `result_types` unless they end in a terminator.
+	pub fn if_else<T, E>(
+		&mut self,
+		cond: Value,
+		result_types: &[Type],
+		f: &mut FunctionBuilder,
+		then: T,
+		els: E,
+	) -> Result<Vec<Value>>
+	where
+		T: FnOnce(&mut Compiler<M>, &mut FunctionBuilder) -> Result<Vec<Value>>,
+		E: FnOnce(&mut Compiler<M>, &mut FunctionBuilder) -> Result<Vec<Value>>,
+	{
+		let then_block = f.create_block();
+		let else_block = f.create_block();
+		let merge_block = f.create_block();
+
+		for ty in result_types {
+			f.append_block_param(merge_block, *ty);
+		}
+
+		f.ins().brnz(cond, then_block, &[]);
+		f.ins().jump(else_block, &[]);
+		f.seal_block(then_block);
+		f.seal_block(else_block);
+
+		f.switch_to_block(then_block);
+		let then_results = then(self, f)?;
+		if falls_through(f) {
+			self.jump(merge_block, &then_results, f)?;
+		}
+
+		f.switch_to_block(else_block);
+		let else_results = els(self, f)?;
+		if falls_through(f) {
+			self.jump(merge_block, &else_results, f)?;
+		}
+
+		f.seal_block(merge_block);
+		f.switch_to_block(merge_block);
+
+		Ok(f.block_params(merge_block).to_vec())
+	}
+
+	/// Re-evaluates `cond` and runs `body` for as long as it is non-zero.
+	/// `body` may use `break_loop` and `continue_loop` without values.
+	pub fn while_loop<C, B>(
+		&mut self,
+		f: &mut FunctionBuilder,
+		cond: C,
+		body: B,
+	) -> Result<()>
+	where
+		C: FnOnce(&mut Compiler<M>, &mut FunctionBuilder) -> Result<Value>,
+		B: FnOnce(&mut Compiler<M>, &mut FunctionBuilder) -> Result<()>,
+	{
+		let header = f.create_block();
+		let body_block = f.create_block();
+		let exit = f.create_block();
+
+		f.ins().jump(header, &[]);
+		f.switch_to_block(header);
+
+		let cond = cond(self, f)?;
+		f.ins().brz(cond, exit, &[]);
+		f.ins().jump(body_block, &[]);
+		f.seal_block(body_block);
+
+		f.switch_to_block(body_block);
+		self.loops.push(LoopCtx { header, exit });
+		let result = body(self, f);
+		self.loops.pop();
+		result?;
+
+		if falls_through(f) {
+			f.ins().jump(header, &[]);
+		}
+
+		f.seal_block(header);
+		f.seal_block(exit);
+		f.switch_to_block(exit);
+
+		Ok(())
+	}
+
+	/// Runs `body` repeatedly until it calls `break_loop`. The loop starts
+	/// with `args` as its parameters, and each `continue_loop` supplies the
+	/// parameters for the next iteration. The values passed to `break_loop`,
+	/// which must be of `result_types`, are returned.
+	pub fn loop_block<B>(
+		&mut self,
+		args: &[Value],
+		result_types: &[Type],
+		f: &mut FunctionBuilder,
+		body: B,
+	) -> Result<Vec<Value>>
+	where
+		B: FnOnce(
+			&mut Compiler<M>,
+			&mut FunctionBuilder,
+			&[Value],
+		) -> Result<()>,
+	{
+		let header = f.create_block();
+		let exit = f.create_block();
+
+		for arg in args {
+			f.append_block_param(header, f.func.dfg.value_type(*arg));
+		}
+
+		for ty in result_types {
+			f.append_block_param(exit, *ty);
+		}
+
+		f.ins().jump(header, args);
+		f.switch_to_block(header);
+
+		let params = f.block_params(header).to_vec();
+		self.loops.push(LoopCtx { header, exit });
+		let result = body(self, f, &params);
+		self.loops.pop();
+		result?;
+
+		if falls_through(f) {
+			self.jump(header, &[], f)?;
+		}
+
+		f.seal_block(header);
+		f.seal_block(exit);
+		f.switch_to_block(exit);
+
+		Ok(f.block_params(exit).to_vec())
+	}
+
+	/// Runs the arm whose constant equals `scrutinee`, or `default` if none
+	/// does, and returns the values it produced. Dense sets of constants are
+	/// lowered to jump tables and sparse ones to a binary search. The
+	/// scrutinee must be a scalar integer.
+	pub fn match_int<D>(
+		&mut self,
+		scrutinee: Value,
+		result_types: &[Type],
+		f: &mut FunctionBuilder,
+		arms: Vec<(u128, MatchArm<M>)>,
+		default: D,
+	) -> Result<Vec<Value>>
+	where
+		D: FnOnce(&mut Compiler<M>, &mut FunctionBuilder) -> Result<Vec<Value>>,
+	{
+		let scrutinee_type = f.func.dfg.value_type(scrutinee);
+		if !scrutinee_type.is_int() {
+			return Err(CompileError::InvalidScrutinee(scrutinee_type));
+		}
+
+		let max = scrutinee_type.bounds(false).1;
+		let mut seen = HashSet::new();
+
+		for (value, _) in &arms {
+			if *value > max {
+				return Err(CompileError::MatchArmOutOfRange {
+					value: *value,
+					scrutinee_type,
+				});
+			}
+
+			if !seen.insert(*value) {
+				return Err(CompileError::DuplicateMatchArm(*value));
+			}
+		}
+
+		let default_block = f.create_block();
+		let merge_block = f.create_block();
+
+		for ty in result_types {
+			f.append_block_param(merge_block, *ty);
+		}
+
+		let mut switch = Switch::new();
+		let arms = arms
+			.into_iter()
+			.map(|(value, arm)| {
+				let block = f.create_block();
+				switch.set_entry(value, block);
+				(block, arm)
+			})
+			.collect::<Vec<_>>();
+
+		switch.emit(f, scrutinee, default_block);
+
+		for (block, arm) in arms {
+			f.seal_block(block);
+			f.switch_to_block(block);
+
+			let results = arm(self, f)?;
+			if falls_through(f) {
+				self.jump(merge_block, &results, f)?;
+			}
+		}
+
+		f.seal_block(default_block);
+		f.switch_to_block(default_block);
+
+		let results = default(self, f)?;
+		if falls_through(f) {
+			self.jump(merge_block, &results, f)?;
+		}
+
+		f.seal_block(merge_block);
+		f.switch_to_block(merge_block);
+
+		Ok(f.block_params(merge_block).to_vec())
+	}
+
+	/// Leaves the innermost loop, passing `values` as its results.
+	pub fn break_loop(
+		&mut self,
+		values: &[Value],
+		f: &mut FunctionBuilder,
+	) -> Result<()> {
+		let exit = self.loops.last().ok_or(CompileError::NotInLoop)?.exit;
+
+		self.jump(exit, values, f)?;
+		self.switch_to_unreachable(f);
+
+		Ok(())
+	}
+
+	/// Starts the next iteration of the innermost loop, passing `values` as
+	/// its parameters.
+	pub fn continue_loop(
+		&mut self,
+		values: &[Value],
+		f: &mut FunctionBuilder,
+	) -> Result<()> {
+		let header = self.loops.last().ok_or(CompileError::NotInLoop)?.header;
+
+		self.jump(header, values, f)?;
+		self.switch_to_unreachable(f);
+
+		Ok(())
+	}
+
+	fn jump(
+		&self,
+		block: Block,
+		values: &[Value],
+		f: &mut FunctionBuilder,
+	) -> Result<()> {
+		let expected = f
+			.block_params(block)
+			.iter()
+			.map(|param| f.func.dfg.value_type(*param))
+			.collect::<Vec<_>>();
+		let found = values
+			.iter()
+			.map(|val| f.func.dfg.value_type(*val))
+			.collect::<Vec<_>>();
+
+		if expected != found {
+			return Err(CompileError::BlockArgumentMismatch {
+				expected,
+				found,
+			});
+		}
+
+		f.ins().jump(block, values);
+
+		Ok(())
+	}
+
+	/// Moves to a fresh block with no predecessors so that callers can keep
+	/// emitting code after a jump that ends the current block.
+	fn switch_to_unreachable(&self, f: &mut FunctionBuilder) {
+		let block = f.create_block();
+		f.seal_block(block);
+		f.switch_to_block(block);
+	}
+}
@@ -0,0 +1,100 @@
+use cranelift::prelude::Type;
+use cranelift_module::{DataId, FuncId, Linkage};
+
+/// Describes the contents of a data object along with any addresses of
+/// functions or other data objects that should be written into it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DataDesc {
+	pub contents: Box<[u8]>,
+	pub align: Option<u64>,
+	pub func_relocs: Vec<(u32, FuncId)>,
+	pub data_relocs: Vec<(u32, DataId, i64)>,
+}
+
+impl DataDesc {
+	pub fn new(contents: Box<[u8]>) -> DataDesc {
+		DataDesc {
+			contents,
+			..DataDesc::default()
+		}
+	}
+
+	pub fn zeroed(size: usize) -> DataDesc {
+		DataDesc::new(vec![0; size].into_boxed_slice())
+	}
+
+	pub fn align(mut self, align: u64) -> DataDesc {
+		self.align = Some(align);
+		self
+	}
+
+	/// Writes the address of `func_id` at `offset` when the data is
+	/// relocated.
+	pub fn func_addr(mut self, offset: u32, func_id: FuncId) -> DataDesc {
+		self.func_relocs.push((offset, func_id));
+		self
+	}
+
+	/// Writes the address of `data_id` plus `addend` at `offset` when the
+	/// data is relocated.
+	pub fn data_addr(
+		mut self,
+		offset: u32,
+		data_id: DataId,
+		addend: i64,
+	) -> DataDesc {
+		self.data_relocs.push((offset, data_id, addend));
+		self
+	}
+}
+
+/// Describes a global holding a single value of `var_type`, as declared by
+/// `Compiler::declare_var`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VarDesc {
+	pub var_type: Type,
+	pub linkage: Linkage,
+	pub writable: bool,
+	pub tls: bool,
+	/// The initial bytes of the value, or `None` to zero-initialize it.
+	pub init: Option<Box<[u8]>>,
+}
+
+impl VarDesc {
+	/// A writable, zero-initialized global.
+	pub fn new(var_type: Type, linkage: Linkage) -> VarDesc {
+		VarDesc {
+			var_type,
+			linkage,
+			writable: true,
+			tls: false,
+			init: None,
+		}
+	}
+
+	pub fn read_only(mut self) -> VarDesc {
+		self.writable = false;
+		self
+	}
+
+	/// Gives every thread its own copy of the global.
+	pub fn thread_local(mut self) -> VarDesc {
+		self.tls = true;
+		self
+	}
+
+	pub fn init(mut self, init: &[u8]) -> VarDesc {
+		self.init = Some(init.into());
+		self
+	}
+}
+
+/// How `Compiler::string_literal` lays out string contents in memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StringMode {
+	/// The bytes followed by a NUL terminator, as C expects.
+	#[default]
+	NulTerminated,
+	/// A pointer-sized length in target byte order followed by the bytes.
+	LengthPrefixed,
+}
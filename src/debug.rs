@@ -0,0 +1,198 @@
+use crate::Compiler;
+use cranelift::{
+	codegen::{
+		ir::{SourceLoc, ValueLabel},
+		MachSrcLoc, ValueLabelsRanges, ValueLocRange,
+	},
+	prelude::*,
+};
+use cranelift_module::{DataId, FuncId, Module};
+use std::collections::{BTreeMap, HashMap};
+
+/// A position in the user's source, interned so that it can be carried on
+/// instructions as a `SourceLoc`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Location {
+	pub(crate) file: usize,
+	pub(crate) line: u32,
+	pub(crate) col: u32,
+}
+
+/// A global declared while variables were enabled.
+#[cfg_attr(not(feature = "object"), allow(dead_code))]
+pub(crate) struct GlobalDebug {
+	pub(crate) data_id: DataId,
+	pub(crate) var_type: Type,
+}
+
+/// A variable named with `name_var` in the function being built.
+pub(crate) struct NamedVar {
+	label: ValueLabel,
+	name: String,
+	var_type: Type,
+}
+
+/// A named variable of a defined function and where its value lives over
+/// the function's machine code.
+#[cfg_attr(not(feature = "object"), allow(dead_code))]
+pub(crate) struct LocalVar {
+	pub(crate) name: String,
+	pub(crate) var_type: Type,
+	pub(crate) ranges: Vec<ValueLocRange>,
+}
+
+/// The debug info of a defined function: machine code offsets that carry a
+/// location and the named variables that are live somewhere in its body.
+#[cfg_attr(not(feature = "object"), allow(dead_code))]
+pub(crate) struct FuncDebug {
+	pub(crate) func_id: FuncId,
+	pub(crate) size: u32,
+	pub(crate) rows: Vec<(u32, Location)>,
+	pub(crate) locals: Vec<LocalVar>,
+}
+
+/// Source locations set through `set_location`, and the line tables and
+/// variables of every function defined once debug info was in use. Every
+/// backend records them, but only object files emit them.
+#[derive(Default)]
+pub(crate) struct DebugInfo {
+	pub(crate) files: Vec<String>,
+	file_ids: HashMap<String, usize>,
+	locations: Vec<Location>,
+	location_ids: HashMap<Location, SourceLoc>,
+	pub(crate) vars_enabled: bool,
+	pub(crate) named_vars: Vec<NamedVar>,
+	pub(crate) globals: BTreeMap<String, GlobalDebug>,
+	pub(crate) funcs: Vec<FuncDebug>,
+}
+
+impl DebugInfo {
+	/// Whether functions defined now should have their debug info recorded.
+	pub(crate) fn is_tracking(&self) -> bool {
+		!self.locations.is_empty() || self.vars_enabled
+	}
+
+	/// Whether there is nothing to emit.
+	#[cfg(feature = "object")]
+	pub(crate) fn is_empty(&self) -> bool {
+		self.funcs.is_empty() && self.globals.is_empty()
+	}
+
+	pub(crate) fn add_global(
+		&mut self,
+		name: &str,
+		data_id: DataId,
+		var_type: Type,
+	) {
+		if self.vars_enabled {
+			self.globals
+				.insert(name.to_owned(), GlobalDebug { data_id, var_type });
+		}
+	}
+
+	pub(crate) fn add_func(
+		&mut self,
+		func_id: FuncId,
+		size: u32,
+		srclocs: &[MachSrcLoc],
+		named_vars: Vec<NamedVar>,
+		value_labels: &ValueLabelsRanges,
+	) {
+		let rows = srclocs
+			.iter()
+			.filter_map(|srcloc| {
+				let location = self.locations.get(srcloc.loc.bits() as usize)?;
+				Some((srcloc.start, *location))
+			})
+			.collect::<Vec<_>>();
+
+		let locals = named_vars
+			.into_iter()
+			.filter_map(|var| {
+				let ranges = value_labels.get(&var.label)?;
+				Some(LocalVar {
+					name: var.name,
+					var_type: var.var_type,
+					ranges: ranges.clone(),
+				})
+			})
+			.collect::<Vec<_>>();
+
+		if !rows.is_empty() || !locals.is_empty() {
+			self.funcs.push(FuncDebug {
+				func_id,
+				size,
+				rows,
+				locals,
+			});
+		}
+	}
+}
+
+fn var_label(var: Variable) -> ValueLabel {
+	ValueLabel::new(var.index())
+}
+
+impl<M: Module> Compiler<M> {
+	/// Attributes every instruction built from now on to `line` and `col` of
+	/// `file`, until the next call. Backends that emit debug info turn these
+	/// into line tables.
+	pub fn set_location(
+		&mut self,
+		file: &str,
+		line: u32,
+		col: u32,
+		f: &mut FunctionBuilder,
+	) {
+		let debug = &mut self.debug;
+
+		let file = match debug.file_ids.get(file) {
+			Some(file) => *file,
+			None => {
+				let id = debug.files.len();
+				debug.files.push(file.to_owned());
+				debug.file_ids.insert(file.to_owned(), id);
+				id
+			}
+		};
+
+		let location = Location { file, line, col };
+		let srcloc = match debug.location_ids.get(&location) {
+			Some(srcloc) => *srcloc,
+			None => {
+				let srcloc = SourceLoc::new(debug.locations.len() as u32);
+				debug.locations.push(location);
+				debug.location_ids.insert(location, srcloc);
+				srcloc
+			}
+		};
+
+		f.set_srcloc(srcloc);
+	}
+
+	/// Enables or disables describing variables in the debug info. Globals
+	/// created with `create_var` or `declare_var` while enabled are emitted
+	/// with their data symbol, and functions defined while enabled track the
+	/// values of variables named with `name_var` or declared with
+	/// `declare_local`.
+	pub fn set_debug_vars(&mut self, enabled: bool) {
+		self.debug.vars_enabled = enabled;
+	}
+
+	/// Names a variable from `new_var` in the debug info of the function
+	/// being built. Only values assigned through `def_var` are tracked.
+	pub fn name_var(&mut self, var: Variable, name: &str, var_type: Type) {
+		self.debug.named_vars.push(NamedVar {
+			label: var_label(var),
+			name: name.to_owned(),
+			var_type,
+		});
+	}
+
+	/// Assigns `val` to `var` like `FunctionBuilder::def_var`, and marks the
+	/// value as holding `var` for the debug info.
+	pub fn def_var(&self, var: Variable, val: Value, f: &mut FunctionBuilder) {
+		f.def_var(var, val);
+		f.set_val_label(val, var_label(var));
+	}
+}
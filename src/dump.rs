@@ -0,0 +1,59 @@
+use crate::{Compiler, Result};
+use cranelift_module::Module;
+use std::{fs, path::Path};
+
+/// The IR and machine code captured for a single function.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FuncDump {
+	/// The CLIF of the function as built, before any optimization.
+	pub clif: String,
+	/// The disassembled machine code, if the backend produced one.
+	pub disasm: Option<String>,
+}
+
+impl<M: Module> Compiler<M> {
+	/// Enables or disables capturing the CLIF and disassembly of every
+	/// function defined from now on.
+	pub fn set_dump(&mut self, enabled: bool) {
+		self.dump_enabled = enabled;
+	}
+
+	pub fn dump(&self, name: &str) -> Option<&FuncDump> {
+		self.dumps.get(name)
+	}
+
+	/// Writes every captured function to `dir` as `<name>.clif` and, when a
+	/// disassembly is available, `<name>.s`. Characters in `name` that aren't
+	/// safe in a file name are escaped as `%XX`.
+	pub fn write_dumps<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+		let dir = dir.as_ref();
+		fs::create_dir_all(dir)?;
+
+		for (name, dump) in &self.dumps {
+			let stem = file_stem(name);
+			fs::write(dir.join(format!("{}.clif", stem)), &dump.clif)?;
+
+			if let Some(disasm) = &dump.disasm {
+				fs::write(dir.join(format!("{}.s", stem)), disasm)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Escapes everything but ASCII letters, digits, `_` and `-`, so that every
+/// function name maps to a distinct file directly inside the dump directory.
+fn file_stem(name: &str) -> String {
+	let mut stem = String::with_capacity(name.len());
+
+	for byte in name.bytes() {
+		if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+			stem.push(byte as char);
+		} else {
+			stem.push_str(&format!("%{:02X}", byte));
+		}
+	}
+
+	stem
+}
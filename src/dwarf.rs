@@ -0,0 +1,569 @@
+use crate::{debug::FuncDebug, CompileError, Compiler, Result};
+use ::object::{
+	write::{
+		Object, Relocation, SectionId as ObjectSectionId, StandardSegment,
+	},
+	BinaryFormat, RelocationEncoding, RelocationKind, SectionKind,
+};
+use cranelift::{
+	codegen::ir::{Endianness, LabelValueLoc},
+	prelude::{isa::TargetIsa, *},
+};
+use cranelift_module::{FuncOrDataId, Linkage, Module};
+use cranelift_object::{ObjectModule, ObjectProduct};
+use gimli::{
+	write::{
+		Address, AttributeValue, DwarfUnit, EndianVec, Expression, FileId,
+		LineProgram, LineString, Location, LocationList, Range, RangeList,
+		Sections, UnitEntryId, Writer,
+	},
+	Encoding, Format, LineEncoding, Register, RunTimeEndian, SectionId,
+};
+use std::{collections::HashMap, env, path::Path};
+use target_lexicon::Architecture;
+
+/// A relocation that a DWARF section needs against either a function or data
+/// symbol, or the start of another DWARF section.
+#[derive(Clone)]
+struct Reloc {
+	offset: usize,
+	size: u8,
+	target: RelocTarget,
+	addend: i64,
+}
+
+#[derive(Clone)]
+enum RelocTarget {
+	Symbol(usize),
+	Section(SectionId),
+}
+
+/// A section writer that records relocations instead of resolving addresses,
+/// which are only known once the object is linked.
+#[derive(Clone)]
+struct RelocWriter {
+	data: EndianVec<RunTimeEndian>,
+	relocs: Vec<Reloc>,
+	relocate_sections: bool,
+}
+
+impl Writer for RelocWriter {
+	type Endian = RunTimeEndian;
+
+	fn endian(&self) -> Self::Endian {
+		self.data.endian()
+	}
+
+	fn len(&self) -> usize {
+		self.data.len()
+	}
+
+	fn write(&mut self, bytes: &[u8]) -> gimli::write::Result<()> {
+		self.data.write(bytes)
+	}
+
+	fn write_at(
+		&mut self,
+		offset: usize,
+		bytes: &[u8],
+	) -> gimli::write::Result<()> {
+		self.data.write_at(offset, bytes)
+	}
+
+	fn write_address(
+		&mut self,
+		address: Address,
+		size: u8,
+	) -> gimli::write::Result<()> {
+		match address {
+			Address::Constant(val) => self.write_udata(val, size),
+			Address::Symbol { symbol, addend } => {
+				self.relocs.push(Reloc {
+					offset: self.len(),
+					size,
+					target: RelocTarget::Symbol(symbol),
+					addend,
+				});
+				self.write_udata(0, size)
+			}
+		}
+	}
+
+	fn write_offset(
+		&mut self,
+		val: usize,
+		section: SectionId,
+		size: u8,
+	) -> gimli::write::Result<()> {
+		if !self.relocate_sections {
+			return self.write_udata(val as u64, size);
+		}
+
+		self.relocs.push(Reloc {
+			offset: self.len(),
+			size,
+			target: RelocTarget::Section(section),
+			addend: val as i64,
+		});
+		self.write_udata(0, size)
+	}
+
+	fn write_offset_at(
+		&mut self,
+		offset: usize,
+		val: usize,
+		section: SectionId,
+		size: u8,
+	) -> gimli::write::Result<()> {
+		// Mach-O refers to other debug sections by plain offsets, everything
+		// else needs them relocated when sections are merged at link time.
+		if !self.relocate_sections {
+			return self.write_udata_at(offset, val as u64, size);
+		}
+
+		self.relocs.push(Reloc {
+			offset,
+			size,
+			target: RelocTarget::Section(section),
+			addend: val as i64,
+		});
+		self.write_udata_at(offset, 0, size)
+	}
+}
+
+/// The DWARF sections describing a module. Addresses in them still refer to
+/// functions and data objects by id, as symbols only exist once the module
+/// is finished.
+pub(crate) struct DebugSections {
+	sections: Sections<RelocWriter>,
+	symbols: Vec<FuncOrDataId>,
+}
+
+/// Builds `.debug_info`, `.debug_line` and the sections they depend on,
+/// describing every function with recorded source locations or variables,
+/// and every global declared while variables were enabled.
+pub(crate) fn build(
+	compiler: &Compiler<ObjectModule>,
+) -> Result<DebugSections> {
+	let debug = &compiler.debug;
+	let isa = compiler.module.isa();
+	let encoding = Encoding {
+		format: Format::Dwarf32,
+		version: 4,
+		address_size: isa.pointer_bytes(),
+	};
+
+	let mut builder = UnitBuilder {
+		dwarf: DwarfUnit::new(encoding),
+		types: HashMap::new(),
+		symbols: Vec::new(),
+		symbol_ids: HashMap::new(),
+	};
+	let dwarf = &mut builder.dwarf;
+
+	let comp_dir = env::current_dir()
+		.map(|dir| dir.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	// The compilation unit is named after the first file a location was set
+	// in, which is normally the main source file.
+	let comp_name = match debug.files.first() {
+		Some(file) if !file.is_empty() => file.clone(),
+		_ => "<unknown>".to_owned(),
+	};
+
+	// Without any locations there is nothing to put in a line program, and
+	// no file to name it after.
+	if !debug.files.is_empty() {
+		dwarf.unit.line_program = LineProgram::new(
+			encoding,
+			LineEncoding::default(),
+			LineString::new(
+				comp_dir.as_bytes(),
+				encoding,
+				&mut dwarf.line_strings,
+			),
+			LineString::new(
+				comp_name.as_bytes(),
+				encoding,
+				&mut dwarf.line_strings,
+			),
+			None,
+		);
+	}
+
+	let file_ids = debug
+		.files
+		.iter()
+		.map(|file| {
+			let path = Path::new(file);
+			let name = match path.file_name() {
+				Some(name) => name.to_string_lossy().into_owned(),
+				None if file.is_empty() => "<unknown>".to_owned(),
+				None => file.clone(),
+			};
+
+			// Bare file names have an empty parent, which DWARF 4 can only
+			// express as the compilation directory.
+			let dir_id = match path.parent() {
+				Some(dir) if !dir.as_os_str().is_empty() => {
+					let dir = LineString::new(
+						dir.to_string_lossy().into_owned(),
+						encoding,
+						&mut dwarf.line_strings,
+					);
+					dwarf.unit.line_program.add_directory(dir)
+				}
+				_ => dwarf.unit.line_program.default_directory(),
+			};
+
+			let name = LineString::new(name, encoding, &mut dwarf.line_strings);
+			dwarf.unit.line_program.add_file(name, dir_id, None)
+		})
+		.collect::<Vec<FileId>>();
+
+	let root = dwarf.unit.root();
+	let producer = dwarf.strings.add(concat!(
+		env!("CARGO_PKG_NAME"),
+		" ",
+		env!("CARGO_PKG_VERSION")
+	));
+	let name = dwarf.strings.add(comp_name);
+	let comp_dir = dwarf.strings.add(comp_dir);
+	let entry = dwarf.unit.get_mut(root);
+	entry.set(gimli::DW_AT_producer, AttributeValue::StringRef(producer));
+	if !debug.files.is_empty() {
+		entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+	}
+	entry.set(gimli::DW_AT_comp_dir, AttributeValue::StringRef(comp_dir));
+	entry.set(
+		gimli::DW_AT_low_pc,
+		AttributeValue::Address(Address::Constant(0)),
+	);
+
+	let mut ranges = Vec::with_capacity(debug.funcs.len());
+
+	for func in &debug.funcs {
+		let decl = compiler
+			.module
+			.declarations()
+			.get_function_decl(func.func_id);
+		let address = builder.symbol(FuncOrDataId::Func(func.func_id), 0);
+		ranges.push(Range::StartLength {
+			begin: address,
+			length: func.size as u64,
+		});
+
+		let dwarf = &mut builder.dwarf;
+		if !func.rows.is_empty() {
+			let line_program = &mut dwarf.unit.line_program;
+			line_program.begin_sequence(Some(address));
+			for (offset, location) in &func.rows {
+				let row = line_program.row();
+				row.address_offset = *offset as u64;
+				row.file = file_ids[location.file];
+				row.line = location.line as u64;
+				row.column = location.col as u64;
+				line_program.generate_row();
+			}
+			line_program.end_sequence(func.size as u64);
+		}
+
+		let name = dwarf.strings.add(decl.name.as_str());
+		let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+		let entry = dwarf.unit.get_mut(subprogram);
+		entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+		if decl.linkage != Linkage::Local {
+			entry.set(gimli::DW_AT_external, AttributeValue::Flag(true));
+		}
+		if let Some((_, first)) = func.rows.first() {
+			entry.set(
+				gimli::DW_AT_decl_file,
+				AttributeValue::FileIndex(Some(file_ids[first.file])),
+			);
+			entry.set(
+				gimli::DW_AT_decl_line,
+				AttributeValue::Udata(first.line as u64),
+			);
+		}
+		entry.set(gimli::DW_AT_low_pc, AttributeValue::Address(address));
+		entry.set(
+			gimli::DW_AT_high_pc,
+			AttributeValue::Udata(func.size as u64),
+		);
+
+		builder.add_locals(subprogram, func, isa);
+	}
+
+	let dwarf = &mut builder.dwarf;
+	let ranges = dwarf.unit.ranges.add(RangeList(ranges));
+	dwarf
+		.unit
+		.get_mut(root)
+		.set(gimli::DW_AT_ranges, AttributeValue::RangeListRef(ranges));
+
+	for (name, var) in &debug.globals {
+		let decl = compiler.module.declarations().get_data_decl(var.data_id);
+		let var_type = builder.base_type(var.var_type);
+
+		// Imports are defined elsewhere and thread-local globals have no fixed
+		// address, so neither is given a location.
+		let address = (decl.linkage.is_definable() && !decl.tls)
+			.then(|| builder.symbol(FuncOrDataId::Data(var.data_id), 0));
+
+		let dwarf = &mut builder.dwarf;
+		let name = dwarf.strings.add(name.as_str());
+		let variable = dwarf.unit.add(root, gimli::DW_TAG_variable);
+		let entry = dwarf.unit.get_mut(variable);
+		entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+		entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(var_type));
+		if decl.linkage != Linkage::Local {
+			entry.set(gimli::DW_AT_external, AttributeValue::Flag(true));
+		}
+
+		if !decl.linkage.is_definable() {
+			entry.set(gimli::DW_AT_declaration, AttributeValue::Flag(true));
+		} else if let Some(address) = address {
+			let mut expr = Expression::new();
+			expr.op_addr(address);
+			entry.set(gimli::DW_AT_location, AttributeValue::Exprloc(expr));
+		}
+	}
+
+	let endian = match isa.endianness() {
+		Endianness::Little => RunTimeEndian::Little,
+		Endianness::Big => RunTimeEndian::Big,
+	};
+	let mut sections = Sections::new(RelocWriter {
+		data: EndianVec::new(endian),
+		relocs: Vec::new(),
+		relocate_sections: isa.triple().binary_format
+			!= target_lexicon::BinaryFormat::Macho,
+	});
+	builder.dwarf.write(&mut sections).map_err(emit_error)?;
+
+	Ok(DebugSections {
+		sections,
+		symbols: builder.symbols,
+	})
+}
+
+impl DebugSections {
+	/// Adds the sections to `product`, relocated against the symbols of the
+	/// functions and data objects they describe.
+	pub(crate) fn add_to(mut self, product: &mut ObjectProduct) -> Result<()> {
+		let symbols = self
+			.symbols
+			.iter()
+			.map(|id| match id {
+				FuncOrDataId::Func(func_id) => {
+					product.function_symbol(*func_id)
+				}
+				FuncOrDataId::Data(data_id) => product.data_symbol(*data_id),
+			})
+			.collect::<Vec<_>>();
+
+		// Every section has to exist before relocations against other
+		// sections can be added.
+		let mut section_ids = HashMap::new();
+		self.sections.for_each_mut(|id, section| -> Result<()> {
+			if !section.data.slice().is_empty() {
+				let data = section.data.take();
+				section_ids.insert(
+					id,
+					add_debug_section(&mut product.object, id, data),
+				);
+			}
+			Ok(())
+		})?;
+
+		self.sections.for_each(|id, section| -> Result<()> {
+			let section_id = match section_ids.get(&id) {
+				Some(section_id) => *section_id,
+				None => return Ok(()),
+			};
+
+			for reloc in &section.relocs {
+				let (symbol, kind) = match reloc.target {
+					RelocTarget::Symbol(index) => {
+						(symbols[index], RelocationKind::Absolute)
+					}
+					// COFF debug sections refer to each other by offsets
+					// relative to the start of the target section.
+					RelocTarget::Section(target) => (
+						product.object.section_symbol(section_ids[&target]),
+						if product.object.format() == BinaryFormat::Coff {
+							RelocationKind::SectionOffset
+						} else {
+							RelocationKind::Absolute
+						},
+					),
+				};
+
+				product
+					.object
+					.add_relocation(
+						section_id,
+						Relocation {
+							offset: reloc.offset as u64,
+							size: reloc.size * 8,
+							kind,
+							encoding: RelocationEncoding::Generic,
+							symbol,
+							addend: reloc.addend,
+						},
+					)
+					.map_err(emit_error)?;
+			}
+
+			Ok(())
+		})
+	}
+}
+
+/// The unit being built, along with the base types already described and
+/// the symbols its addresses refer to by index.
+struct UnitBuilder {
+	dwarf: DwarfUnit,
+	types: HashMap<Type, UnitEntryId>,
+	symbols: Vec<FuncOrDataId>,
+	symbol_ids: HashMap<FuncOrDataId, usize>,
+}
+
+impl UnitBuilder {
+	fn symbol(&mut self, id: FuncOrDataId, addend: i64) -> Address {
+		let symbols = &mut self.symbols;
+		let symbol = *self.symbol_ids.entry(id).or_insert_with(|| {
+			symbols.push(id);
+			symbols.len() - 1
+		});
+
+		Address::Symbol { symbol, addend }
+	}
+
+	fn base_type(&mut self, ty: Type) -> UnitEntryId {
+		if let Some(id) = self.types.get(&ty) {
+			return *id;
+		}
+
+		let encoding = if ty.is_float() {
+			gimli::DW_ATE_float
+		} else if ty.is_bool() {
+			gimli::DW_ATE_boolean
+		} else if ty.is_ref() {
+			gimli::DW_ATE_address
+		} else if ty.is_int() {
+			gimli::DW_ATE_signed
+		} else {
+			gimli::DW_ATE_unsigned
+		};
+
+		let root = self.dwarf.unit.root();
+		let id = self.dwarf.unit.add(root, gimli::DW_TAG_base_type);
+		let name = self.dwarf.strings.add(ty.to_string());
+		let entry = self.dwarf.unit.get_mut(id);
+		entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+		entry.set(gimli::DW_AT_encoding, AttributeValue::Encoding(encoding));
+		entry.set(
+			gimli::DW_AT_byte_size,
+			AttributeValue::Udata(ty.bytes() as u64),
+		);
+
+		self.types.insert(ty, id);
+		id
+	}
+
+	/// Describes the named variables of `func` as children of its
+	/// subprogram, each with a location list covering where its value lives.
+	fn add_locals(
+		&mut self,
+		subprogram: UnitEntryId,
+		func: &FuncDebug,
+		isa: &dyn TargetIsa,
+	) {
+		for local in &func.locals {
+			let locations = local
+				.ranges
+				.iter()
+				.filter_map(|range| {
+					Some(Location::StartLength {
+						begin: self.symbol(
+							FuncOrDataId::Func(func.func_id),
+							range.start as i64,
+						),
+						length: (range.end - range.start) as u64,
+						data: location_expr(range.loc, isa)?,
+					})
+				})
+				.collect::<Vec<_>>();
+
+			if locations.is_empty() {
+				continue;
+			}
+
+			let var_type = self.base_type(local.var_type);
+			let locations =
+				self.dwarf.unit.locations.add(LocationList(locations));
+			let name = self.dwarf.strings.add(local.name.as_str());
+			let variable =
+				self.dwarf.unit.add(subprogram, gimli::DW_TAG_variable);
+			let entry = self.dwarf.unit.get_mut(variable);
+			entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+			entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(var_type));
+			entry.set(
+				gimli::DW_AT_location,
+				AttributeValue::LocationListRef(locations),
+			);
+		}
+	}
+}
+
+/// Describes where a value lives, or `None` if the register or stack pointer
+/// has no DWARF number on this target.
+fn location_expr(
+	loc: LabelValueLoc,
+	isa: &dyn TargetIsa,
+) -> Option<Expression> {
+	let mut expr = Expression::new();
+
+	match loc {
+		LabelValueLoc::Reg(reg) => {
+			let reg = isa.map_regalloc_reg_to_dwarf(reg).ok()?;
+			expr.op_reg(Register(reg));
+		}
+		LabelValueLoc::SPOffset(offset) => {
+			expr.op_breg(stack_pointer(isa)?, offset);
+		}
+	}
+
+	Some(expr)
+}
+
+fn stack_pointer(isa: &dyn TargetIsa) -> Option<Register> {
+	match isa.triple().architecture {
+		Architecture::X86_64 => Some(Register(7)),
+		Architecture::Aarch64(_) => Some(Register(31)),
+		Architecture::S390x => Some(Register(15)),
+		_ => None,
+	}
+}
+
+fn add_debug_section(
+	object: &mut Object,
+	id: SectionId,
+	data: Vec<u8>,
+) -> ObjectSectionId {
+	let name = match object.format() {
+		BinaryFormat::MachO => id.name().replacen('.', "__", 1),
+		_ => id.name().to_owned(),
+	};
+	let segment = object.segment_name(StandardSegment::Debug).to_vec();
+
+	let section =
+		object.add_section(segment, name.into_bytes(), SectionKind::Debug);
+	object.set_section_data(section, data, 1);
+	section
+}
+
+fn emit_error<E: ToString>(err: E) -> CompileError {
+	CompileError::Emit(err.to_string())
+}
@@ -0,0 +1,424 @@
+use crate::FuncError;
+use cranelift::codegen::{
+	ir::{types::Type, Signature},
+	isa::CallConv,
+	settings::SetError,
+	verifier::VerifierErrors,
+	CodegenError,
+};
+use cranelift_module::ModuleError;
+use std::{error::Error, fmt, io};
+
+pub type Result<T, E = CompileError> = std::result::Result<T, E>;
+
+/// Errors produced while building a module with a `Compiler`.
+#[derive(Debug)]
+pub enum CompileError {
+	/// A variable was used that was never created.
+	UnknownVariable(String),
+
+	/// A function was called or looked up that was never declared.
+	UnknownFunction(String),
+
+	/// A data object was used that was never created.
+	UnknownData(String),
+
+	/// A variable was accessed with a type other than the one it was created
+	/// with.
+	VariableTypeMismatch {
+		name: String,
+		expected: Type,
+		found: Type,
+	},
+
+	/// A store targeted a read-only variable.
+	ReadOnlyVariable(String),
+
+	/// A variable initializer does not match the size of the variable.
+	InitializerSize {
+		name: String,
+		expected: usize,
+		found: usize,
+	},
+
+	/// An imported variable was given an initializer.
+	ImportedInitializer(String),
+
+	/// A thread-local variable was declared for a target or backend that
+	/// can't access thread-local storage.
+	UnsupportedTls(String),
+
+	/// A stack allocation was given an alignment that is not a power of two.
+	InvalidStackAlignment(u32),
+
+	/// A stack allocation, padded for its alignment, does not fit in a
+	/// stack slot.
+	StackAllocTooLarge {
+		size: u32,
+		align: u32,
+	},
+
+	/// A typed stack slot was accessed with a different type.
+	StackTypeMismatch {
+		expected: Type,
+		found: Type,
+	},
+
+	/// A data object was given an alignment that is not a power of two.
+	InvalidAlignment {
+		name: String,
+		align: u64,
+	},
+
+	/// A relocation does not fit inside the contents of its data object.
+	RelocationOutOfBounds {
+		name: String,
+		offset: u32,
+		size: usize,
+	},
+
+	/// A function was declared again with a different calling convention.
+	CallConvMismatch {
+		name: String,
+		declared: CallConv,
+		found: CallConv,
+	},
+
+	/// A function was imported again with a different signature.
+	SignatureMismatch {
+		name: String,
+		declared: Box<Signature>,
+		found: Box<Signature>,
+	},
+
+	/// A struct field index is past the end of its layout.
+	UnknownField {
+		index: usize,
+		len: usize,
+	},
+
+	/// A struct field holding a nested record was loaded or stored directly.
+	AggregateField(usize),
+
+	/// A value stored to a struct field does not match the field's type.
+	FieldTypeMismatch {
+		index: usize,
+		expected: Type,
+		found: Type,
+	},
+
+	/// An array element was accessed with a type of the wrong size.
+	ElementSizeMismatch {
+		expected: u32,
+		found: Type,
+	},
+
+	/// A call passed the wrong number of arguments.
+	ArgumentCount {
+		name: String,
+		expected: usize,
+		found: usize,
+	},
+
+	/// A call passed an argument of the wrong type.
+	ArgumentType {
+		name: String,
+		index: usize,
+		expected: Type,
+		found: Type,
+	},
+
+	/// `pop_scope` was called without a matching `push_scope`.
+	UnbalancedScope,
+
+	/// `break_loop` or `continue_loop` was used outside of a loop.
+	NotInLoop,
+
+	/// A branch passed values that don't match the parameters of its target
+	/// block.
+	BlockArgumentMismatch {
+		expected: Vec<Type>,
+		found: Vec<Type>,
+	},
+
+	/// Two arms of a `match_int` share the same constant.
+	DuplicateMatchArm(u128),
+
+	/// A `match_int` scrutinee is not a scalar integer.
+	InvalidScrutinee(Type),
+
+	/// A `match_int` arm constant does not fit in the type of the scrutinee.
+	MatchArmOutOfRange {
+		value: u128,
+		scrutinee_type: Type,
+	},
+
+	/// Functions that were declared with a definable linkage but never
+	/// defined.
+	UndefinedFunctions(Vec<String>),
+
+	/// A function was looked up that has no definition in this module, such
+	/// as an import or a function whose definition failed.
+	FunctionNotDefined(String),
+
+	/// A compiled function was looked up before the module was finalized
+	/// since its last definition.
+	NotFinalized,
+
+	/// Every function that failed to compile in batch mode.
+	Functions(Vec<FuncError>),
+
+	/// The IR built for a function failed verification. `listing` holds the
+	/// function's CLIF with the offending instructions annotated.
+	Verifier {
+		func: String,
+		errors: VerifierErrors,
+		listing: String,
+	},
+
+	/// The target could not be set up.
+	Target(String),
+
+	/// The finished module could not be emitted.
+	Emit(String),
+
+	/// An invalid codegen setting was applied.
+	Settings(SetError),
+
+	Codegen(CodegenError),
+
+	Module(Box<ModuleError>),
+
+	Io(io::Error),
+
+	/// An error raised by user code, such as a builder closure.
+	Custom(Box<dyn Error + Send + Sync>),
+}
+
+impl Error for CompileError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::Verifier { errors, .. } => Some(errors),
+			Self::Settings(err) => Some(err),
+			Self::Codegen(err) => Some(err),
+			Self::Module(err) => Some(&**err),
+			Self::Io(err) => Some(err),
+			Self::Custom(err) => Some(&**err),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for CompileError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::UnknownVariable(name) => {
+				write!(f, "unknown variable: {}", name)
+			}
+			Self::UnknownFunction(name) => {
+				write!(f, "unknown function: {}", name)
+			}
+			Self::UnknownData(name) => {
+				write!(f, "unknown data object: {}", name)
+			}
+			Self::VariableTypeMismatch {
+				name,
+				expected,
+				found,
+			} => write!(
+				f,
+				"variable {} has type {} but was accessed as {}",
+				name, expected, found
+			),
+			Self::ReadOnlyVariable(name) => {
+				write!(f, "variable {} is read-only", name)
+			}
+			Self::InitializerSize {
+				name,
+				expected,
+				found,
+			} => write!(
+				f,
+				"initializer for variable {} is {} bytes but {} bytes were expected",
+				name, found, expected
+			),
+			Self::ImportedInitializer(name) => write!(
+				f,
+				"imported variable {} cannot have an initializer",
+				name
+			),
+			Self::UnsupportedTls(name) => write!(
+				f,
+				"thread-local variable {} is not supported by this target",
+				name
+			),
+			Self::InvalidStackAlignment(align) => write!(
+				f,
+				"stack alignment {} is not a power of two",
+				align
+			),
+			Self::StackAllocTooLarge { size, align } => write!(
+				f,
+				"stack allocation of {} bytes aligned to {} is too large",
+				size, align
+			),
+			Self::StackTypeMismatch { expected, found } => write!(
+				f,
+				"stack slot has type {} but was accessed as {}",
+				expected, found
+			),
+			Self::InvalidAlignment { name, align } => write!(
+				f,
+				"alignment {} of data object {} is not a power of two",
+				align, name
+			),
+			Self::RelocationOutOfBounds { name, offset, size } => write!(
+				f,
+				"relocation at offset {} does not fit in data object {} of {} bytes",
+				offset, name, size
+			),
+			Self::CallConvMismatch {
+				name,
+				declared,
+				found,
+			} => write!(
+				f,
+				"function {} was declared with calling convention {} but is now used with {}",
+				name, declared, found
+			),
+			Self::SignatureMismatch {
+				name,
+				declared,
+				found,
+			} => write!(
+				f,
+				"function {} was declared with signature {} but is now used with {}",
+				name, declared, found
+			),
+			Self::UnknownField { index, len } => write!(
+				f,
+				"field {} is out of range for a struct with {} fields",
+				index, len
+			),
+			Self::AggregateField(index) => write!(
+				f,
+				"field {} is a nested struct and must be accessed through its address",
+				index
+			),
+			Self::FieldTypeMismatch {
+				index,
+				expected,
+				found,
+			} => write!(
+				f,
+				"field {} has type {} but a value of type {} was stored",
+				index, expected, found
+			),
+			Self::ElementSizeMismatch { expected, found } => write!(
+				f,
+				"array elements are {} bytes but were accessed as {}",
+				expected, found
+			),
+			Self::ArgumentCount {
+				name,
+				expected,
+				found,
+			} => write!(
+				f,
+				"function {} expects {} arguments but {} were given",
+				name, expected, found
+			),
+			Self::ArgumentType {
+				name,
+				index,
+				expected,
+				found,
+			} => write!(
+				f,
+				"argument {} of function {} has type {} but {} was expected",
+				index, name, found, expected
+			),
+			Self::UnbalancedScope => {
+				write!(f, "pop_scope called without a matching push_scope")
+			}
+			Self::NotInLoop => {
+				write!(f, "break or continue used outside of a loop")
+			}
+			Self::BlockArgumentMismatch { expected, found } => write!(
+				f,
+				"branch passes values of types {:?} but {:?} were expected",
+				found, expected
+			),
+			Self::DuplicateMatchArm(value) => {
+				write!(f, "match arm {} appears more than once", value)
+			}
+			Self::InvalidScrutinee(ty) => {
+				write!(f, "cannot match on a value of type {}", ty)
+			}
+			Self::MatchArmOutOfRange {
+				value,
+				scrutinee_type,
+			} => write!(
+				f,
+				"match arm {} does not fit in scrutinee of type {}",
+				value, scrutinee_type
+			),
+			Self::UndefinedFunctions(names) => write!(
+				f,
+				"functions declared but never defined: {}",
+				names.join(", ")
+			),
+			Self::FunctionNotDefined(name) => write!(
+				f,
+				"function {} has no definition in this module",
+				name
+			),
+			Self::NotFinalized => {
+				write!(f, "module must be finalized before looking up functions")
+			}
+			Self::Functions(failures) => {
+				write!(f, "{} functions failed to compile", failures.len())?;
+
+				for failure in failures {
+					write!(f, "\n{}", failure)?;
+				}
+
+				Ok(())
+			}
+			Self::Verifier { func, listing, .. } => {
+				write!(f, "verifier errors in function {}:\n{}", func, listing)
+			}
+			Self::Target(msg) => write!(f, "target error: {}", msg),
+			Self::Emit(msg) => write!(f, "emit error: {}", msg),
+			Self::Settings(err) => write!(f, "settings error: {}", err),
+			Self::Codegen(err) => write!(f, "codegen error: {}", err),
+			Self::Module(err) => write!(f, "module error: {}", err),
+			Self::Io(err) => write!(f, "I/O error: {}", err),
+			Self::Custom(err) => err.fmt(f),
+		}
+	}
+}
+
+impl From<SetError> for CompileError {
+	fn from(err: SetError) -> Self {
+		Self::Settings(err)
+	}
+}
+
+impl From<CodegenError> for CompileError {
+	fn from(err: CodegenError) -> Self {
+		Self::Codegen(err)
+	}
+}
+
+impl From<ModuleError> for CompileError {
+	fn from(err: ModuleError) -> Self {
+		Self::Module(Box::new(err))
+	}
+}
+
+impl From<io::Error> for CompileError {
+	fn from(err: io::Error) -> Self {
+		Self::Io(err)
+	}
+}
@@ -0,0 +1,96 @@
+use crate::{CompileError, Compiler, CompilerBuilder, Result};
+use cranelift::codegen::settings::TlsModel;
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::default_libcall_names;
+
+impl CompilerBuilder {
+	/// Makes `ptr` the address of the imported function or global `name` in
+	/// a JIT-compiled module, taking precedence over symbols of the host
+	/// process. Only used by `build_jit`.
+	pub fn symbol(mut self, name: &str, ptr: *const u8) -> CompilerBuilder {
+		self.symbols.push((name.to_owned(), ptr as usize));
+		self
+	}
+
+	/// Builds a compiler that JIT-compiles for the host ISA.
+	pub fn build_jit(self) -> Result<Compiler<JITModule>> {
+		if let Some(target) = &self.target {
+			return Err(CompileError::Target(format!(
+				"JIT compilation can only target the host, not {}",
+				target
+			)));
+		}
+
+		let isa_builder = cranelift_native::builder().map_err(|msg| {
+			CompileError::Target(format!(
+				"host machine is not supported: {}",
+				msg
+			))
+		})?;
+		let isa = self.finish_isa(
+			isa_builder,
+			&[("use_colocated_libcalls", "false"), ("is_pic", "false")],
+		)?;
+
+		if isa.flags().is_pic() {
+			return Err(CompileError::Target(
+				"JIT compilation does not support position-independent code"
+					.to_owned(),
+			));
+		}
+
+		if isa.flags().tls_model() != TlsModel::None {
+			return Err(CompileError::Target(
+				"JIT compilation does not support thread-local storage"
+					.to_owned(),
+			));
+		}
+
+		let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+		builder.symbols(
+			self.symbols
+				.into_iter()
+				.map(|(name, addr)| (name, addr as *const u8)),
+		);
+
+		Ok(Compiler::new(JITModule::new(builder)))
+	}
+}
+
+impl Compiler<JITModule> {
+	/// Creates a compiler that JIT-compiles for the host ISA with default
+	/// settings.
+	pub fn jit() -> Result<Compiler<JITModule>> {
+		CompilerBuilder::new().build_jit()
+	}
+
+	/// Performs all outstanding relocations so compiled functions can be
+	/// called. Must be called before `get_func`.
+	pub fn finalize(&mut self) -> Result<()> {
+		self.take_failures()?;
+		self.check_defined()?;
+		self.module.finalize_definitions();
+		self.finalized = true;
+
+		Ok(())
+	}
+
+	/// Returns a pointer to the compiled code of a function defined in this
+	/// module. Fails if a function was defined since the last `finalize`.
+	pub fn get_func(&self, name: &str) -> Result<*const u8> {
+		let func_id = self
+			.functions
+			.get(name)
+			.ok_or_else(|| CompileError::UnknownFunction(name.to_owned()))?;
+
+		if !self.defined_funcs.contains(func_id) {
+			return Err(CompileError::FunctionNotDefined(name.to_owned()));
+		}
+
+		if !self.finalized {
+			return Err(CompileError::NotFinalized);
+		}
+
+		Ok(self.module.get_finalized_function(*func_id))
+	}
+}
@@ -0,0 +1,262 @@
+use crate::{CompileError, Compiler, DataDesc, Result};
+use cranelift::prelude::{isa::TargetFrontendConfig, *};
+use cranelift_module::Module;
+
+/// The type of a single field of a `StructLayout`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+	Scalar(Type),
+	/// A pointer sized for the target.
+	Pointer,
+	Struct(StructLayout),
+}
+
+/// The C-compatible memory layout of a record, with every field placed at
+/// the next offset that satisfies its alignment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructLayout {
+	fields: Vec<(u32, FieldType)>,
+	size: u32,
+	align: u32,
+}
+
+impl StructLayout {
+	pub fn new(
+		fields: &[FieldType],
+		config: TargetFrontendConfig,
+	) -> StructLayout {
+		let mut offset = 0;
+		let mut align = 1;
+		let mut laid_out = Vec::with_capacity(fields.len());
+
+		for field in fields {
+			let (field, field_size, field_align) = match field {
+				FieldType::Scalar(ty) => {
+					(FieldType::Scalar(*ty), ty.bytes(), ty.bytes().max(1))
+				}
+				FieldType::Pointer => {
+					let ty = config.pointer_type();
+					(FieldType::Scalar(ty), ty.bytes(), ty.bytes())
+				}
+				FieldType::Struct(layout) => {
+					(field.clone(), layout.size, layout.align)
+				}
+			};
+
+			offset = align_to(offset, field_align);
+			align = align.max(field_align);
+			laid_out.push((offset, field));
+			offset += field_size;
+		}
+
+		StructLayout {
+			fields: laid_out,
+			size: align_to(offset, align),
+			align,
+		}
+	}
+
+	pub fn size(&self) -> u32 {
+		self.size
+	}
+
+	pub fn align(&self) -> u32 {
+		self.align
+	}
+
+	pub fn len(&self) -> usize {
+		self.fields.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.fields.is_empty()
+	}
+
+	pub fn offset(&self, index: usize) -> Result<u32> {
+		Ok(self.field(index)?.0)
+	}
+
+	pub fn field_type(&self, index: usize) -> Result<&FieldType> {
+		Ok(&self.field(index)?.1)
+	}
+
+	/// Describes a zero-initialized data object large enough to hold the
+	/// record, for use with `Compiler::create_named_data`.
+	pub fn zeroed(&self) -> DataDesc {
+		DataDesc::zeroed(self.size as usize).align(self.align as u64)
+	}
+
+	fn field(&self, index: usize) -> Result<&(u32, FieldType)> {
+		self.fields.get(index).ok_or(CompileError::UnknownField {
+			index,
+			len: self.fields.len(),
+		})
+	}
+
+	/// Returns the address of a field of the record at `base`, which is how
+	/// nested records are reached.
+	pub fn field_addr(
+		&self,
+		base: Value,
+		index: usize,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		let (offset, _) = self.field(index)?;
+
+		Ok(f.ins().iadd_imm(base, *offset as i64))
+	}
+
+	pub fn load_field(
+		&self,
+		base: Value,
+		index: usize,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		let (offset, ty) = self.scalar_field(index)?;
+
+		Ok(f.ins().load(ty, MemFlags::new(), base, offset as i32))
+	}
+
+	pub fn store_field(
+		&self,
+		base: Value,
+		index: usize,
+		val: Value,
+		f: &mut FunctionBuilder,
+	) -> Result<()> {
+		let (offset, ty) = self.scalar_field(index)?;
+		let val_type = f.func.dfg.value_type(val);
+
+		if val_type != ty {
+			return Err(CompileError::FieldTypeMismatch {
+				index,
+				expected: ty,
+				found: val_type,
+			});
+		}
+
+		f.ins().store(MemFlags::new(), val, base, offset as i32);
+
+		Ok(())
+	}
+
+	fn scalar_field(&self, index: usize) -> Result<(u32, Type)> {
+		match self.field(index)? {
+			(offset, FieldType::Scalar(ty)) => Ok((*offset, *ty)),
+			_ => Err(CompileError::AggregateField(index)),
+		}
+	}
+}
+
+fn align_to(offset: u32, align: u32) -> u32 {
+	(offset + align - 1) & !(align - 1)
+}
+
+impl<M: Module> Compiler<M> {
+	pub fn struct_layout(&self, fields: &[FieldType]) -> StructLayout {
+		StructLayout::new(fields, self.module.target_config())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cranelift::prelude::isa::CallConv;
+	use target_lexicon::PointerWidth;
+
+	fn config(pointer_width: PointerWidth) -> TargetFrontendConfig {
+		TargetFrontendConfig {
+			default_call_conv: CallConv::SystemV,
+			pointer_width,
+		}
+	}
+
+	fn offsets(layout: &StructLayout) -> Vec<u32> {
+		(0..layout.len())
+			.map(|i| layout.offset(i).unwrap())
+			.collect()
+	}
+
+	#[test]
+	fn fields_are_padded_to_their_alignment() {
+		let layout = StructLayout::new(
+			&[
+				FieldType::Scalar(types::I8),
+				FieldType::Scalar(types::I32),
+				FieldType::Scalar(types::I16),
+				FieldType::Scalar(types::F64),
+				FieldType::Scalar(types::I8),
+			],
+			config(PointerWidth::U64),
+		);
+
+		assert_eq!(offsets(&layout), [0, 4, 8, 16, 24]);
+		assert_eq!(layout.size(), 32);
+		assert_eq!(layout.align(), 8);
+	}
+
+	#[test]
+	fn empty_struct() {
+		let layout = StructLayout::new(&[], config(PointerWidth::U64));
+
+		assert!(layout.is_empty());
+		assert_eq!(layout.size(), 0);
+		assert_eq!(layout.align(), 1);
+	}
+
+	#[test]
+	fn pointers_follow_target_width() {
+		let fields = [FieldType::Scalar(types::I8), FieldType::Pointer];
+
+		let wide = StructLayout::new(&fields, config(PointerWidth::U64));
+		assert_eq!(offsets(&wide), [0, 8]);
+		assert_eq!(wide.size(), 16);
+		assert_eq!(wide.field_type(1).unwrap(), &FieldType::Scalar(types::I64));
+
+		let narrow = StructLayout::new(&fields, config(PointerWidth::U32));
+		assert_eq!(offsets(&narrow), [0, 4]);
+		assert_eq!(narrow.size(), 8);
+		assert_eq!(narrow.align(), 4);
+		assert_eq!(
+			narrow.field_type(1).unwrap(),
+			&FieldType::Scalar(types::I32)
+		);
+	}
+
+	#[test]
+	fn nested_structs_keep_their_size_and_alignment() {
+		let inner = StructLayout::new(
+			&[FieldType::Scalar(types::I32), FieldType::Scalar(types::I8)],
+			config(PointerWidth::U64),
+		);
+		assert_eq!(inner.size(), 8);
+		assert_eq!(inner.align(), 4);
+
+		let outer = StructLayout::new(
+			&[
+				FieldType::Scalar(types::I8),
+				FieldType::Struct(inner.clone()),
+				FieldType::Scalar(types::I8),
+			],
+			config(PointerWidth::U64),
+		);
+
+		assert_eq!(offsets(&outer), [0, 4, 12]);
+		assert_eq!(outer.size(), 16);
+		assert_eq!(outer.align(), 4);
+		assert_eq!(outer.field_type(1).unwrap(), &FieldType::Struct(inner));
+	}
+
+	#[test]
+	fn unknown_field() {
+		let layout = StructLayout::new(
+			&[FieldType::Scalar(types::I32)],
+			config(PointerWidth::U64),
+		);
+
+		assert!(matches!(
+			layout.offset(1),
+			Err(CompileError::UnknownField { index: 1, len: 1 })
+		));
+	}
+}
@@ -1,14 +1,74 @@
-use anyhow::Result;
-use cranelift::{codegen::ir::FuncRef, prelude::*};
-use cranelift_module::{DataContext, DataId, FuncId, Linkage, Module};
-use std::collections::HashMap;
+use cranelift::{
+	codegen::{
+		ir::{Endianness, FuncRef, SigRef},
+		print_errors::pretty_verifier_error,
+		settings::TlsModel,
+	},
+	prelude::*,
+};
+use cranelift_module::{
+	DataContext, DataId, FuncId, FuncOrDataId, Linkage, Module,
+};
+use std::collections::{HashMap, HashSet};
+
+mod array;
+mod batch;
+mod builder;
+mod control;
+mod data;
+mod debug;
+mod dump;
+#[cfg(feature = "object")]
+mod dwarf;
+mod error;
+#[cfg(feature = "jit")]
+mod jit;
+mod layout;
+#[cfg(feature = "object")]
+mod object;
+mod scope;
+mod signature;
+mod stack;
+
+pub use array::{Array, ArrayLen, BOUNDS_CHECK_TRAP};
+pub use batch::FuncError;
+pub use builder::CompilerBuilder;
+pub use control::MatchArm;
+pub use data::{DataDesc, StringMode, VarDesc};
+pub use dump::FuncDump;
+pub use error::{CompileError, Result};
+pub use layout::{FieldType, StructLayout};
+pub use signature::FuncSig;
+pub use stack::StackVar;
+
+struct GlobalVar {
+	data_id: DataId,
+	var_type: Type,
+	writable: bool,
+}
 
 pub struct Compiler<M: Module> {
 	pub module: M,
 	data_id_counter: usize,
 	var_id_counter: usize,
-	vars: HashMap<String, DataId>,
+	vars: HashMap<String, GlobalVar>,
+	data: HashMap<String, DataId>,
+	literals: HashMap<(Box<[u8]>, u64), DataId>,
+	string_mode: StringMode,
 	functions: HashMap<String, FuncId>,
+	imports: HashMap<String, FuncId>,
+	defined_funcs: HashSet<FuncId>,
+	finalized: bool,
+	func_refs: HashMap<FuncId, FuncRef>,
+	sig_refs: HashMap<Signature, SigRef>,
+	building: bool,
+	scopes: Vec<scope::Scope>,
+	loops: Vec<control::LoopCtx>,
+	dump_enabled: bool,
+	dumps: HashMap<String, FuncDump>,
+	batch: bool,
+	failures: Vec<FuncError>,
+	debug: debug::DebugInfo,
 }
 
 impl<M: Module> Compiler<M> {
@@ -18,57 +78,211 @@ impl<M: Module> Compiler<M> {
 			data_id_counter: 0,
 			var_id_counter: 0,
 			vars: HashMap::new(),
+			data: HashMap::new(),
+			literals: HashMap::new(),
+			string_mode: StringMode::default(),
 			functions: HashMap::new(),
+			imports: HashMap::new(),
+			defined_funcs: HashSet::new(),
+			finalized: false,
+			func_refs: HashMap::new(),
+			sig_refs: HashMap::new(),
+			building: false,
+			scopes: vec![scope::Scope::new()],
+			loops: Vec::new(),
+			dump_enabled: false,
+			dumps: HashMap::new(),
+			batch: false,
+			failures: Vec::new(),
+			debug: debug::DebugInfo::default(),
 		}
 	}
 
 	pub fn compile_func<F>(
 		&mut self,
 		name: &str,
-		params: &[Type],
-		ret: Option<Type>,
+		sig: &FuncSig,
 		linkage: Linkage,
 		builder: F,
 	) -> Result<FuncId>
 	where
 		F: Fn(&mut Compiler<M>, &mut FunctionBuilder, FuncId) -> Result<()>,
 	{
-		let mut sig = self.module.make_signature();
+		let func_id = self.declare_func(name, sig, linkage)?;
 
-		for param in params {
-			sig.params.push(AbiParam::new(*param));
-		}
+		self.define_func(func_id, builder)?;
+
+		Ok(func_id)
+	}
+
+	/// Declares a function without defining it, so that it can be referenced
+	/// before its body is built with `define_func`.
+	pub fn declare_func(
+		&mut self,
+		name: &str,
+		sig: &FuncSig,
+		linkage: Linkage,
+	) -> Result<FuncId> {
+		let sig = sig.to_signature(self.module.make_signature());
 
-		if let Some(ret) = ret {
-			sig.returns.push(AbiParam::new(ret));
+		let func_id = self.declare_function(name, linkage, &sig)?;
+
+		self.functions.insert(name.to_owned(), func_id);
+
+		Ok(func_id)
+	}
+
+	/// Builds and defines the body of a declared function. In batch mode a
+	/// failure is recorded instead of returned and the function is left
+	/// undefined.
+	pub fn define_func<F>(&mut self, func_id: FuncId, builder: F) -> Result<()>
+	where
+		F: Fn(&mut Compiler<M>, &mut FunctionBuilder, FuncId) -> Result<()>,
+	{
+		match self.try_define_func(func_id, builder) {
+			Err(error) if self.batch => {
+				let decl =
+					self.module.declarations().get_function_decl(func_id);
+				self.failures.push(FuncError {
+					func: decl.name.clone(),
+					error,
+				});
+				Ok(())
+			}
+			result => result,
 		}
+	}
 
-		let func_id = self.module.declare_function(name, linkage, &sig)?;
+	fn try_define_func<F>(&mut self, func_id: FuncId, builder: F) -> Result<()>
+	where
+		F: Fn(&mut Compiler<M>, &mut FunctionBuilder, FuncId) -> Result<()>,
+	{
+		let decl = self.module.declarations().get_function_decl(func_id);
+		let name = decl.name.clone();
+		let sig = decl.signature.clone();
 
 		let mut ctx = self.module.make_context();
 		let mut fn_builder_ctx = FunctionBuilderContext::new();
 		ctx.func = cranelift::codegen::ir::Function::with_name_signature(
-			ExternalName::testcase(name),
+			ExternalName::testcase(&name),
 			sig,
 		);
 
+		if self.debug.vars_enabled {
+			ctx.func.dfg.collect_debug_info();
+		}
+
 		let mut f = FunctionBuilder::new(&mut ctx.func, &mut fn_builder_ctx);
 
-		builder(self, &mut f, func_id)?;
+		// Function and signature references, locals, named variables and loops
+		// are only valid inside the function they were declared in, so keep
+		// the caller's around in case this definition is nested inside another
+		// builder.
+		let outer_func_refs = std::mem::take(&mut self.func_refs);
+		let outer_sig_refs = std::mem::take(&mut self.sig_refs);
+		let outer_building = std::mem::replace(&mut self.building, true);
+		let outer_scopes =
+			std::mem::replace(&mut self.scopes, vec![scope::Scope::new()]);
+		let outer_named_vars = std::mem::take(&mut self.debug.named_vars);
+		let outer_loops = std::mem::take(&mut self.loops);
+		let result = builder(self, &mut f, func_id);
+		self.func_refs = outer_func_refs;
+		self.sig_refs = outer_sig_refs;
+		self.building = outer_building;
+		self.scopes = outer_scopes;
+		let named_vars =
+			std::mem::replace(&mut self.debug.named_vars, outer_named_vars);
+		self.loops = outer_loops;
+		result?;
 
 		f.seal_all_blocks();
 		f.finalize();
 
-		cranelift::codegen::verifier::verify_function(
-			&ctx.func,
-			self.module.isa().flags(),
-		)?;
+		if self.module.isa().flags().enable_verifier() {
+			cranelift::codegen::verifier::verify_function(
+				&ctx.func,
+				self.module.isa().flags(),
+			)
+			.map_err(|errors| CompileError::Verifier {
+				func: name.clone(),
+				listing: pretty_verifier_error(&ctx.func, None, errors.clone()),
+				errors,
+			})?;
+		}
+
+		// Defining the function optimizes its IR in place, so capture the CLIF
+		// before handing it over.
+		let clif = self.dump_enabled.then(|| ctx.func.display().to_string());
+		ctx.set_disasm(self.dump_enabled);
 
 		self.module.define_function(func_id, &mut ctx)?;
 
-		self.functions.insert(name.to_owned(), func_id);
+		if self.debug.is_tracking() {
+			if let Some(compiled) = ctx.compiled_code() {
+				self.debug.add_func(
+					func_id,
+					compiled.buffer.total_size(),
+					compiled.buffer.get_srclocs_sorted(),
+					named_vars,
+					&compiled.value_labels_ranges,
+				);
+			}
+		}
 
-		Ok(func_id)
+		if let Some(clif) = clif {
+			let disasm = ctx
+				.compiled_code()
+				.and_then(|compiled| compiled.disasm.clone());
+			self.dumps.insert(name, FuncDump { clif, disasm });
+		}
+
+		self.defined_funcs.insert(func_id);
+		self.finalized = false;
+
+		Ok(())
+	}
+
+	/// Fails with a list of every function that was declared with a definable
+	/// linkage but never given a body.
+	pub fn check_defined(&self) -> Result<()> {
+		let mut undefined = self
+			.module
+			.declarations()
+			.get_functions()
+			.filter(|(func_id, decl)| {
+				decl.linkage.is_definable()
+					&& !self.defined_funcs.contains(func_id)
+			})
+			.map(|(_, decl)| decl.name.clone())
+			.collect::<Vec<_>>();
+
+		if !undefined.is_empty() {
+			undefined.sort_unstable();
+			return Err(CompileError::UndefinedFunctions(undefined));
+		}
+
+		Ok(())
+	}
+
+	fn declare_function(
+		&mut self,
+		name: &str,
+		linkage: Linkage,
+		sig: &Signature,
+	) -> Result<FuncId> {
+		if let Some(FuncOrDataId::Func(func_id)) = self.module.get_name(name) {
+			let decl = self.module.declarations().get_function_decl(func_id);
+
+			if decl.signature.call_conv != sig.call_conv {
+				return Err(CompileError::CallConvMismatch {
+					name: name.to_owned(),
+					declared: decl.signature.call_conv,
+					found: sig.call_conv,
+				});
+			}
+		}
+
+		Ok(self.module.declare_function(name, linkage, sig)?)
 	}
 
 	pub fn new_var(&mut self) -> Variable {
@@ -78,62 +292,428 @@ impl<M: Module> Compiler<M> {
 	}
 
 	pub fn create_data(&mut self, data: Box<[u8]>) -> Result<DataId> {
-		let data_id = self.module.declare_data(
-			&format!("data_{}", {
-				let id = self.data_id_counter;
-				self.data_id_counter += 1;
-				id
-			}),
+		let name = self.next_data_name();
+
+		self.create_named_data(
+			&name,
 			Linkage::Local,
 			false,
-			false,
-		)?;
+			&DataDesc::new(data),
+		)
+	}
+
+	fn next_data_name(&mut self) -> String {
+		let id = self.data_id_counter;
+		self.data_id_counter += 1;
+		format!("data_{}", id)
+	}
+
+	pub fn create_named_data(
+		&mut self,
+		name: &str,
+		linkage: Linkage,
+		writable: bool,
+		desc: &DataDesc,
+	) -> Result<DataId> {
+		if let Some(align) = desc.align {
+			if !align.is_power_of_two() {
+				return Err(CompileError::InvalidAlignment {
+					name: name.to_owned(),
+					align,
+				});
+			}
+		}
+
+		let pointer_bytes =
+			self.module.target_config().pointer_bytes() as usize;
+		let reloc_offsets = desc
+			.func_relocs
+			.iter()
+			.map(|(offset, _)| *offset)
+			.chain(desc.data_relocs.iter().map(|(offset, _, _)| *offset));
+
+		for offset in reloc_offsets {
+			if offset as usize + pointer_bytes > desc.contents.len() {
+				return Err(CompileError::RelocationOutOfBounds {
+					name: name.to_owned(),
+					offset,
+					size: desc.contents.len(),
+				});
+			}
+		}
+
+		let data_id =
+			self.module.declare_data(name, linkage, writable, false)?;
+
 		let mut ctx = DataContext::new();
-		ctx.define(data);
+		ctx.define(desc.contents.clone());
+
+		if let Some(align) = desc.align {
+			ctx.set_align(align);
+		}
+
+		for (offset, func_id) in &desc.func_relocs {
+			let func_ref = self.module.declare_func_in_data(*func_id, &mut ctx);
+			ctx.write_function_addr(*offset, func_ref);
+		}
+
+		for (offset, target, addend) in &desc.data_relocs {
+			let data_ref = self.module.declare_data_in_data(*target, &mut ctx);
+			ctx.write_data_addr(*offset, data_ref, *addend);
+		}
+
 		self.module.define_data(data_id, &ctx)?;
+		self.data.insert(name.to_owned(), data_id);
 
 		Ok(data_id)
 	}
 
+	pub fn data_ptr(
+		&mut self,
+		name: &str,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		let data_id = *self
+			.data
+			.get(name)
+			.ok_or_else(|| CompileError::UnknownData(name.to_owned()))?;
+
+		Ok(self.global_ptr(data_id, f))
+	}
+
+	fn global_ptr(
+		&mut self,
+		data_id: DataId,
+		f: &mut FunctionBuilder,
+	) -> Value {
+		let data_ref = self.module.declare_data_in_func(data_id, f.func);
+		f.ins()
+			.global_value(self.module.target_config().pointer_type(), data_ref)
+	}
+
+	pub fn set_string_mode(&mut self, mode: StringMode) {
+		self.string_mode = mode;
+	}
+
+	/// Returns a pointer to a read-only copy of `s` laid out according to the
+	/// current `StringMode`. Identical literals share a single data object.
+	pub fn string_literal(
+		&mut self,
+		s: &str,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		let pointer_bytes =
+			self.module.target_config().pointer_bytes() as usize;
+
+		let (contents, align) = match self.string_mode {
+			StringMode::NulTerminated => {
+				let mut contents = Vec::with_capacity(s.len() + 1);
+				contents.extend_from_slice(s.as_bytes());
+				contents.push(0);
+				(contents, 1)
+			}
+			StringMode::LengthPrefixed => {
+				let len = s.len() as u64;
+
+				let mut contents = Vec::with_capacity(pointer_bytes + s.len());
+				match self.module.isa().endianness() {
+					Endianness::Little => contents
+						.extend_from_slice(&len.to_le_bytes()[..pointer_bytes]),
+					Endianness::Big => contents.extend_from_slice(
+						&len.to_be_bytes()[8 - pointer_bytes..],
+					),
+				}
+				contents.extend_from_slice(s.as_bytes());
+				(contents, pointer_bytes as u64)
+			}
+		};
+
+		self.literal(contents.into_boxed_slice(), align, f)
+	}
+
+	/// Returns a pointer to a read-only copy of `bytes`. Identical literals
+	/// share a single data object.
+	pub fn bytes_literal(
+		&mut self,
+		bytes: &[u8],
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		self.literal(bytes.into(), 1, f)
+	}
+
+	fn literal(
+		&mut self,
+		contents: Box<[u8]>,
+		align: u64,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		let key = (contents, align);
+
+		let data_id = match self.literals.get(&key) {
+			Some(data_id) => *data_id,
+			None => {
+				let name = self.next_data_name();
+				let desc = DataDesc::new(key.0.clone()).align(align);
+				let data_id = self.create_named_data(
+					&name,
+					Linkage::Local,
+					false,
+					&desc,
+				)?;
+				self.literals.insert(key, data_id);
+				data_id
+			}
+		};
+
+		Ok(self.global_ptr(data_id, f))
+	}
+
 	pub fn import_func(
 		&mut self,
 		name: &str,
-		params: &[Type],
-		ret: Option<Type>,
+		sig: &FuncSig,
 		f: &mut FunctionBuilder,
 	) -> Result<FuncRef> {
-		let mut sig = self.module.make_signature();
+		let sig = sig.to_signature(self.module.make_signature());
 
-		for param in params {
-			sig.params.push(AbiParam::new(*param));
-		}
+		let func_id = match self.imports.get(name) {
+			Some(func_id) => {
+				let decl =
+					self.module.declarations().get_function_decl(*func_id);
+
+				if decl.signature != sig {
+					return Err(CompileError::SignatureMismatch {
+						name: name.to_owned(),
+						declared: Box::new(decl.signature.clone()),
+						found: Box::new(sig),
+					});
+				}
+
+				*func_id
+			}
+			None => {
+				let func_id =
+					self.declare_function(name, Linkage::Import, &sig)?;
+				self.imports.insert(name.to_owned(), func_id);
+				func_id
+			}
+		};
+
+		Ok(self.func_ref(func_id, f))
+	}
 
-		if let Some(ret) = ret {
-			sig.returns.push(AbiParam::new(ret));
+	fn lookup_func(&self, name: &str) -> Result<FuncId> {
+		self.functions
+			.get(name)
+			.or_else(|| self.imports.get(name))
+			.copied()
+			.ok_or_else(|| CompileError::UnknownFunction(name.to_owned()))
+	}
+
+	/// Calls a function declared in this module by name and returns the
+	/// results of the call.
+	pub fn call<'a>(
+		&mut self,
+		name: &str,
+		args: &[Value],
+		f: &'a mut FunctionBuilder,
+	) -> Result<&'a [Value]> {
+		let func_id = self.lookup_func(name)?;
+
+		check_args(
+			name,
+			&self
+				.module
+				.declarations()
+				.get_function_decl(func_id)
+				.signature,
+			args,
+			f,
+		)?;
+
+		let func_ref = self.func_ref(func_id, f);
+		let call = f.ins().call(func_ref, args);
+
+		Ok(f.inst_results(call))
+	}
+
+	/// Returns the address of a function declared in this module, for use
+	/// with `call_indirect` or storing in data.
+	pub fn func_addr(
+		&mut self,
+		name: &str,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		let func_id = self.lookup_func(name)?;
+		let func_ref = self.func_ref(func_id, f);
+
+		Ok(f.ins()
+			.func_addr(self.module.target_config().pointer_type(), func_ref))
+	}
+
+	/// Calls the function at `callee`, which must have the signature `sig`,
+	/// and returns the results of the call.
+	pub fn call_indirect<'a>(
+		&mut self,
+		sig: &FuncSig,
+		callee: Value,
+		args: &[Value],
+		f: &'a mut FunctionBuilder,
+	) -> Result<&'a [Value]> {
+		let sig = sig.to_signature(self.module.make_signature());
+
+		check_args("<indirect>", &sig, args, f)?;
+
+		let sig_ref = if self.building {
+			*self
+				.sig_refs
+				.entry(sig)
+				.or_insert_with_key(|sig| f.import_signature(sig.clone()))
+		} else {
+			f.import_signature(sig)
+		};
+		let call = f.ins().call_indirect(sig_ref, callee, args);
+
+		Ok(f.inst_results(call))
+	}
+
+	/// References are only cached while `define_func` is building a function,
+	/// as the cache can't tell which function a builder passed in from
+	/// elsewhere belongs to.
+	fn func_ref(
+		&mut self,
+		func_id: FuncId,
+		f: &mut FunctionBuilder,
+	) -> FuncRef {
+		if !self.building {
+			return self.module.declare_func_in_func(func_id, f.func);
 		}
 
-		let func = self.module.declare_function(name, Linkage::Import, &sig)?;
+		*self.func_refs.entry(func_id).or_insert_with(|| {
+			self.module.declare_func_in_func(func_id, f.func)
+		})
+	}
+
+	/// Creates a writable global holding a single value of `var_type`,
+	/// zero-initialized unless `init` provides its bytes.
+	pub fn create_var(
+		&mut self,
+		name: &str,
+		var_type: Type,
+		init: Option<&[u8]>,
+	) -> Result<DataId> {
+		let desc = VarDesc::new(var_type, Linkage::Local);
 
-		Ok(self.module.declare_func_in_func(func, f.func))
+		match init {
+			Some(init) => self.declare_var(name, &desc.init(init)),
+			None => self.declare_var(name, &desc),
+		}
 	}
 
-	pub fn create_var(&mut self, name: &str) -> Result<DataId> {
-		let data_id =
-			self.module
-				.declare_data(name, Linkage::Local, true, false)?;
-		let mut ctx = DataContext::new();
-		ctx.define(Box::new([0; std::mem::size_of::<f64>()]));
-		self.module.define_data(data_id, &ctx)?;
-		self.vars.insert(name.to_owned(), data_id);
+	/// Declares a global described by `desc`. Globals with `Linkage::Import`
+	/// are defined elsewhere and cannot take an initializer, all others are
+	/// zero-initialized unless `desc.init` provides their bytes. Thread-local
+	/// globals are only available when emitting object files for targets
+	/// with a supported TLS model.
+	pub fn declare_var(
+		&mut self,
+		name: &str,
+		desc: &VarDesc,
+	) -> Result<DataId> {
+		let VarDesc {
+			var_type,
+			linkage,
+			writable,
+			tls,
+			..
+		} = *desc;
+		let init = desc.init.as_deref();
+		let size = var_type.bytes() as usize;
+
+		match init {
+			Some(_) if !linkage.is_definable() => {
+				return Err(CompileError::ImportedInitializer(name.to_owned()));
+			}
+			Some(init) if init.len() != size => {
+				return Err(CompileError::InitializerSize {
+					name: name.to_owned(),
+					expected: size,
+					found: init.len(),
+				});
+			}
+			_ => {}
+		}
+
+		if tls && !self.supports_tls() {
+			return Err(CompileError::UnsupportedTls(name.to_owned()));
+		}
+
+		let data_id = self.module.declare_data(name, linkage, writable, tls)?;
+
+		if linkage.is_definable() {
+			let mut ctx = DataContext::new();
+			ctx.set_align(var_type.bytes() as u64);
+			match init {
+				Some(init) => ctx.define(init.into()),
+				None => ctx.define_zeroinit(size),
+			}
+
+			self.module.define_data(data_id, &ctx)?;
+		}
+
+		self.debug.add_global(name, data_id, var_type);
+		self.vars.insert(
+			name.to_owned(),
+			GlobalVar {
+				data_id,
+				var_type,
+				writable,
+			},
+		);
 
 		Ok(data_id)
 	}
 
-	pub fn var_ptr(&mut self, name: &str, f: &mut FunctionBuilder) -> Value {
-		let data_id = self.vars[name];
-		let data_ref = self.module.declare_data_in_func(data_id, f.func);
-		f.ins()
-			.global_value(self.module.target_config().pointer_type(), data_ref)
+	/// Whether the backend can lower accesses to thread-local globals with
+	/// the configured TLS model. The JIT never sets one.
+	fn supports_tls(&self) -> bool {
+		let isa = self.module.isa();
+
+		matches!(
+			(isa.name(), isa.flags().tls_model()),
+			("x64", TlsModel::ElfGd | TlsModel::Macho)
+				| ("aarch64", TlsModel::ElfGd)
+		)
+	}
+
+	fn var(&self, name: &str) -> Result<&GlobalVar> {
+		self.vars
+			.get(name)
+			.ok_or_else(|| CompileError::UnknownVariable(name.to_owned()))
+	}
+
+	pub fn var_ptr(
+		&mut self,
+		name: &str,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		let data_id = self.var(name)?.data_id;
+
+		Ok(self.global_ptr(data_id, f))
+	}
+
+	fn check_var_type(&self, name: &str, var_type: Type) -> Result<&GlobalVar> {
+		let var = self.var(name)?;
+
+		if var.var_type != var_type {
+			return Err(CompileError::VariableTypeMismatch {
+				name: name.to_owned(),
+				expected: var.var_type,
+				found: var_type,
+			});
+		}
+
+		Ok(var)
 	}
 
 	pub fn load_var(
@@ -141,9 +721,11 @@ impl<M: Module> Compiler<M> {
 		name: &str,
 		var_type: Type,
 		f: &mut FunctionBuilder,
-	) -> Value {
-		let ptr = self.var_ptr(name, f);
-		f.ins().load(var_type, MemFlags::new(), ptr, 0)
+	) -> Result<Value> {
+		self.check_var_type(name, var_type)?;
+
+		let ptr = self.var_ptr(name, f)?;
+		Ok(f.ins().load(var_type, MemFlags::new(), ptr, 0))
 	}
 
 	pub fn store_var(
@@ -151,8 +733,47 @@ impl<M: Module> Compiler<M> {
 		name: &str,
 		val: Value,
 		f: &mut FunctionBuilder,
-	) {
-		let ptr = self.var_ptr(name, f);
+	) -> Result<()> {
+		if !self
+			.check_var_type(name, f.func.dfg.value_type(val))?
+			.writable
+		{
+			return Err(CompileError::ReadOnlyVariable(name.to_owned()));
+		}
+
+		let ptr = self.var_ptr(name, f)?;
 		f.ins().store(MemFlags::new(), val, ptr, 0);
+
+		Ok(())
+	}
+}
+
+fn check_args(
+	name: &str,
+	sig: &Signature,
+	args: &[Value],
+	f: &FunctionBuilder,
+) -> Result<()> {
+	if sig.params.len() != args.len() {
+		return Err(CompileError::ArgumentCount {
+			name: name.to_owned(),
+			expected: sig.params.len(),
+			found: args.len(),
+		});
 	}
+
+	for (i, (param, arg)) in sig.params.iter().zip(args).enumerate() {
+		let arg_type = f.func.dfg.value_type(*arg);
+
+		if param.value_type != arg_type {
+			return Err(CompileError::ArgumentType {
+				name: name.to_owned(),
+				index: i,
+				expected: param.value_type,
+				found: arg_type,
+			});
+		}
+	}
+
+	Ok(())
 }
@@ -0,0 +1,78 @@
+use crate::{dwarf, CompileError, Compiler, CompilerBuilder, Result};
+use cranelift::prelude::*;
+use cranelift_module::default_libcall_names;
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use std::{fs, path::Path, str::FromStr};
+use target_lexicon::{BinaryFormat, Triple};
+
+impl CompilerBuilder {
+	/// Builds a compiler that emits a relocatable object file named `name`
+	/// for the configured target, or the host if none was set.
+	pub fn build_object(self, name: &str) -> Result<Compiler<ObjectModule>> {
+		let triple = match &self.target {
+			Some(triple) => Triple::from_str(triple).map_err(|err| {
+				CompileError::Target(format!(
+					"invalid target triple {}: {}",
+					triple, err
+				))
+			})?,
+			None => Triple::host(),
+		};
+
+		// Thread-local globals are accessed through the platform's dynamic
+		// TLS model unless a different one is set explicitly.
+		let tls_model = match triple.binary_format {
+			BinaryFormat::Elf => "elf_gd",
+			BinaryFormat::Macho => "macho",
+			_ => "none",
+		};
+
+		let isa_builder = isa::lookup(triple)
+			.map_err(|err| CompileError::Target(err.to_string()))?;
+		let isa = self.finish_isa(
+			isa_builder,
+			&[("is_pic", "true"), ("tls_model", tls_model)],
+		)?;
+
+		let builder = ObjectBuilder::new(isa, name, default_libcall_names())?;
+
+		Ok(Compiler::new(ObjectModule::new(builder)))
+	}
+}
+
+impl Compiler<ObjectModule> {
+	/// Creates a compiler that emits a relocatable object file named `name`
+	/// for the given target triple with default settings.
+	pub fn object(triple: &str, name: &str) -> Result<Compiler<ObjectModule>> {
+		CompilerBuilder::new().target(triple).build_object(name)
+	}
+
+	/// Emits the object file. If any functions or globals were recorded for
+	/// debug info through `set_location` or `set_debug_vars`, DWARF debug
+	/// info is included.
+	pub fn finish(mut self) -> Result<Vec<u8>> {
+		self.take_failures()?;
+		self.check_defined()?;
+
+		let debug = if self.debug.is_empty() {
+			None
+		} else {
+			Some(dwarf::build(&self)?)
+		};
+		let mut product = self.module.finish();
+
+		if let Some(debug) = debug {
+			debug.add_to(&mut product)?;
+		}
+
+		product
+			.emit()
+			.map_err(|err| CompileError::Emit(err.to_string()))
+	}
+
+	pub fn finish_to_path<P: AsRef<Path>>(self, path: P) -> Result<()> {
+		fs::write(path, self.finish()?)?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,98 @@
+use crate::{CompileError, Compiler, Result};
+use cranelift::prelude::*;
+use cranelift_module::Module;
+use std::collections::HashMap;
+
+pub(crate) struct Local {
+	var: Variable,
+	var_type: Type,
+}
+
+pub(crate) type Scope = HashMap<String, Local>;
+
+impl<M: Module> Compiler<M> {
+	pub fn push_scope(&mut self) {
+		self.scopes.push(Scope::new());
+	}
+
+	/// Discards every local declared since the matching `push_scope`. The
+	/// outermost scope of a function is never popped.
+	pub fn pop_scope(&mut self) -> Result<()> {
+		if self.scopes.len() <= 1 {
+			return Err(CompileError::UnbalancedScope);
+		}
+
+		self.scopes.pop();
+
+		Ok(())
+	}
+
+	/// Declares a local in the innermost scope, shadowing any local of the
+	/// same name in this or an enclosing scope.
+	pub fn declare_local(
+		&mut self,
+		name: &str,
+		var_type: Type,
+		init: Value,
+		f: &mut FunctionBuilder,
+	) -> Result<Variable> {
+		let init_type = f.func.dfg.value_type(init);
+
+		if init_type != var_type {
+			return Err(CompileError::VariableTypeMismatch {
+				name: name.to_owned(),
+				expected: var_type,
+				found: init_type,
+			});
+		}
+
+		let var = self.new_var();
+		f.declare_var(var, var_type);
+		self.name_var(var, name, var_type);
+		self.def_var(var, init, f);
+
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.insert(name.to_owned(), Local { var, var_type });
+		}
+
+		Ok(var)
+	}
+
+	fn local(&self, name: &str) -> Result<&Local> {
+		self.scopes
+			.iter()
+			.rev()
+			.find_map(|scope| scope.get(name))
+			.ok_or_else(|| CompileError::UnknownVariable(name.to_owned()))
+	}
+
+	pub fn get_local(
+		&self,
+		name: &str,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		Ok(f.use_var(self.local(name)?.var))
+	}
+
+	pub fn set_local(
+		&self,
+		name: &str,
+		val: Value,
+		f: &mut FunctionBuilder,
+	) -> Result<()> {
+		let local = self.local(name)?;
+		let val_type = f.func.dfg.value_type(val);
+
+		if local.var_type != val_type {
+			return Err(CompileError::VariableTypeMismatch {
+				name: name.to_owned(),
+				expected: local.var_type,
+				found: val_type,
+			});
+		}
+
+		self.def_var(local.var, val, f);
+
+		Ok(())
+	}
+}
@@ -0,0 +1,80 @@
+use cranelift::prelude::{isa::CallConv, *};
+
+/// Describes the parameters and return values of a function independently of
+/// any module, so the same description can be used for definitions and
+/// imports.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FuncSig {
+	pub params: Vec<AbiParam>,
+	pub returns: Vec<AbiParam>,
+	/// Calling convention to use, or the ISA default when `None`.
+	pub call_conv: Option<CallConv>,
+}
+
+impl FuncSig {
+	pub fn new() -> FuncSig {
+		FuncSig::default()
+	}
+
+	pub fn from_types(params: &[Type], returns: &[Type]) -> FuncSig {
+		FuncSig {
+			params: params.iter().map(|ty| AbiParam::new(*ty)).collect(),
+			returns: returns.iter().map(|ty| AbiParam::new(*ty)).collect(),
+			call_conv: None,
+		}
+	}
+
+	pub fn param(self, ty: Type) -> FuncSig {
+		self.abi_param(AbiParam::new(ty))
+	}
+
+	/// Adds a parameter that the caller sign-extends to register width, as C
+	/// expects for `signed char`, `short` and friends.
+	pub fn param_sext(self, ty: Type) -> FuncSig {
+		self.abi_param(AbiParam::new(ty).sext())
+	}
+
+	/// Adds a parameter that the caller zero-extends to register width, as C
+	/// expects for `bool`, `unsigned char` and friends.
+	pub fn param_uext(self, ty: Type) -> FuncSig {
+		self.abi_param(AbiParam::new(ty).uext())
+	}
+
+	pub fn abi_param(mut self, param: AbiParam) -> FuncSig {
+		self.params.push(param);
+		self
+	}
+
+	pub fn ret(self, ty: Type) -> FuncSig {
+		self.abi_ret(AbiParam::new(ty))
+	}
+
+	pub fn ret_sext(self, ty: Type) -> FuncSig {
+		self.abi_ret(AbiParam::new(ty).sext())
+	}
+
+	pub fn ret_uext(self, ty: Type) -> FuncSig {
+		self.abi_ret(AbiParam::new(ty).uext())
+	}
+
+	pub fn abi_ret(mut self, ret: AbiParam) -> FuncSig {
+		self.returns.push(ret);
+		self
+	}
+
+	pub fn call_conv(mut self, call_conv: CallConv) -> FuncSig {
+		self.call_conv = Some(call_conv);
+		self
+	}
+
+	pub(crate) fn to_signature(&self, mut sig: Signature) -> Signature {
+		sig.params.extend_from_slice(&self.params);
+		sig.returns.extend_from_slice(&self.returns);
+
+		if let Some(call_conv) = self.call_conv {
+			sig.call_conv = call_conv;
+		}
+
+		sig
+	}
+}
@@ -0,0 +1,107 @@
+use crate::{CompileError, Compiler, Result};
+use cranelift::{codegen::ir::StackSlot, prelude::*};
+use cranelift_module::Module;
+
+/// Addressable storage in the stack frame of the function being built.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackVar {
+	slot: StackSlot,
+	align: u32,
+	ptr_type: Type,
+	var_type: Option<Type>,
+}
+
+impl StackVar {
+	pub fn addr(&self, f: &mut FunctionBuilder) -> Value {
+		let addr = f.ins().stack_addr(self.ptr_type, self.slot, 0);
+
+		// Slots are only guaranteed to be aligned to the word size, so larger
+		// alignments are reached by rounding up inside an oversized slot.
+		if self.align > self.ptr_type.bytes() {
+			let addr = f.ins().iadd_imm(addr, self.align as i64 - 1);
+			f.ins().band_imm(addr, -(self.align as i64))
+		} else {
+			addr
+		}
+	}
+
+	pub fn load(
+		&self,
+		var_type: Type,
+		f: &mut FunctionBuilder,
+	) -> Result<Value> {
+		self.check_type(var_type)?;
+
+		let addr = self.addr(f);
+		Ok(f.ins().load(var_type, MemFlags::new(), addr, 0))
+	}
+
+	pub fn store(&self, val: Value, f: &mut FunctionBuilder) -> Result<()> {
+		self.check_type(f.func.dfg.value_type(val))?;
+
+		let addr = self.addr(f);
+		f.ins().store(MemFlags::new(), val, addr, 0);
+
+		Ok(())
+	}
+
+	fn check_type(&self, found: Type) -> Result<()> {
+		match self.var_type {
+			Some(expected) if expected != found => {
+				Err(CompileError::StackTypeMismatch { expected, found })
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+impl<M: Module> Compiler<M> {
+	/// Reserves `size` bytes aligned to `align` in the current function's
+	/// stack frame.
+	pub fn stack_alloc(
+		&mut self,
+		size: u32,
+		align: u32,
+		f: &mut FunctionBuilder,
+	) -> Result<StackVar> {
+		if !align.is_power_of_two() {
+			return Err(CompileError::InvalidStackAlignment(align));
+		}
+
+		let ptr_type = self.module.target_config().pointer_type();
+		let padding = if align > ptr_type.bytes() {
+			align - 1
+		} else {
+			0
+		};
+		let padded_size = size
+			.checked_add(padding)
+			.ok_or(CompileError::StackAllocTooLarge { size, align })?;
+		let slot = f.create_sized_stack_slot(StackSlotData::new(
+			StackSlotKind::ExplicitSlot,
+			padded_size,
+		));
+
+		Ok(StackVar {
+			slot,
+			align,
+			ptr_type,
+			var_type: None,
+		})
+	}
+
+	/// Reserves a stack slot holding a single value of `var_type`, whose
+	/// loads and stores are checked against that type.
+	pub fn stack_var(
+		&mut self,
+		var_type: Type,
+		f: &mut FunctionBuilder,
+	) -> Result<StackVar> {
+		let var = self.stack_alloc(var_type.bytes(), var_type.bytes(), f)?;
+
+		Ok(StackVar {
+			var_type: Some(var_type),
+			..var
+		})
+	}
+}
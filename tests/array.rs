@@ -0,0 +1,45 @@
+#![cfg(feature = "jit")]
+
+use clif_builder_util::{Array, Compiler, FuncSig};
+use cranelift::prelude::*;
+use cranelift_module::Linkage;
+use std::mem;
+
+#[test]
+fn wide_indices_are_checked_before_narrowing() {
+	let mut compiler = Compiler::jit().unwrap();
+	compiler.set_dump(true);
+	let sig = FuncSig::new()
+		.param(types::I64)
+		.param(types::I64)
+		.ret(types::I64);
+
+	compiler
+		.compile_func("get", &sig, Linkage::Export, |_, f, _| {
+			let block = f.create_block();
+			f.append_block_params_for_function_params(block);
+			f.switch_to_block(block);
+			f.seal_block(block);
+			let ptr = f.block_params(block)[0];
+			let index = f.block_params(block)[1];
+
+			let index = f.ins().uextend(types::I128, index);
+			let array = Array::fixed(ptr, 4, 8);
+			let val = array.load(types::I64, index, f)?;
+			f.ins().return_(&[val]);
+
+			Ok(())
+		})
+		.unwrap();
+	compiler.finalize().unwrap();
+
+	let clif = &compiler.dump("get").unwrap().clif;
+	let trap = clif.find("trapz").unwrap();
+	let narrow = clif.find("ireduce").unwrap();
+	assert!(trap < narrow);
+
+	let get: extern "C" fn(*const i64, i64) -> i64 =
+		unsafe { mem::transmute(compiler.get_func("get").unwrap()) };
+	let values = [10, 11, 12, 13];
+	assert_eq!(get(values.as_ptr(), 2), 12);
+}
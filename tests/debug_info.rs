@@ -0,0 +1,243 @@
+#![cfg(feature = "object")]
+
+use clif_builder_util::{Compiler, CompilerBuilder, FuncSig};
+use cranelift::prelude::*;
+use cranelift_module::Linkage;
+use cranelift_object::ObjectModule;
+use object::{
+	Object, ObjectSection, ObjectSymbol, RelocationKind, RelocationTarget,
+};
+
+type Dwarf = gimli::Dwarf<Vec<u8>>;
+type Reader<'a> = gimli::EndianSlice<'a, gimli::LittleEndian>;
+
+fn compiler(name: &str) -> Compiler<ObjectModule> {
+	CompilerBuilder::new()
+		.target("x86_64-unknown-linux-gnu")
+		.build_object(name)
+		.unwrap()
+}
+
+/// The contents of section `name` with its relocations applied, as a linker
+/// would before a debugger reads it.
+fn relocated_section(file: &object::File, name: &str) -> Vec<u8> {
+	let section = match file.section_by_name(name) {
+		Some(section) => section,
+		None => return Vec::new(),
+	};
+	let mut data = section.data().unwrap().to_vec();
+
+	for (offset, reloc) in section.relocations() {
+		let base = match reloc.target() {
+			RelocationTarget::Symbol(index) => {
+				file.symbol_by_index(index).unwrap().address()
+			}
+			_ => 0,
+		};
+		let value = base.wrapping_add(reloc.addend() as u64);
+		let offset = offset as usize;
+
+		match reloc.size() {
+			32 => data[offset..offset + 4]
+				.copy_from_slice(&(value as u32).to_le_bytes()),
+			64 => {
+				data[offset..offset + 8].copy_from_slice(&value.to_le_bytes())
+			}
+			size => panic!("unexpected relocation size {}", size),
+		}
+	}
+
+	data
+}
+
+fn load_dwarf(bytes: &[u8]) -> Dwarf {
+	let file = object::File::parse(bytes).unwrap();
+	Dwarf::load(|id| -> Result<_, gimli::Error> {
+		Ok(relocated_section(&file, id.name()))
+	})
+	.unwrap()
+}
+
+fn read_str(
+	dwarf: &gimli::Dwarf<Reader>,
+	unit: &gimli::Unit<Reader>,
+	value: gimli::AttributeValue<Reader>,
+) -> String {
+	dwarf
+		.attr_string(unit, value)
+		.unwrap()
+		.to_string_lossy()
+		.into_owned()
+}
+
+#[test]
+fn line_info_for_bare_and_nested_files() {
+	let mut compiler = compiler("debug_info");
+	let sig = FuncSig::new().param(types::I32).ret(types::I32);
+
+	compiler
+		.compile_func("main", &sig, Linkage::Export, |c, f, _| {
+			let block = f.create_block();
+			f.append_block_params_for_function_params(block);
+			f.switch_to_block(block);
+			f.seal_block(block);
+
+			let param = f.block_params(block)[0];
+			c.set_location("main.x", 1, 1, f);
+			let x = f.ins().imul(param, param);
+			c.set_location("lib/util.x", 7, 3, f);
+			let y = f.ins().iadd_imm(x, 2);
+			c.set_location("main.x", 2, 1, f);
+			f.ins().return_(&[y]);
+
+			Ok(())
+		})
+		.unwrap();
+
+	let bytes = compiler.finish().unwrap();
+	let dwarf = load_dwarf(&bytes);
+	let dwarf = dwarf.borrow(|section| {
+		gimli::EndianSlice::new(section, gimli::LittleEndian)
+	});
+
+	let header = dwarf.units().next().unwrap().unwrap();
+	let unit = dwarf.unit(header).unwrap();
+	let program = unit.line_program.clone().unwrap();
+	let mut rows = program.rows();
+	let mut found = Vec::new();
+
+	while let Some((header, row)) = rows.next_row().unwrap() {
+		if row.end_sequence() {
+			continue;
+		}
+
+		let file = row.file(header).unwrap();
+		let dir = match file.directory(header) {
+			Some(dir) if file.directory_index() != 0 => {
+				read_str(&dwarf, &unit, dir)
+			}
+			_ => String::new(),
+		};
+		let name = read_str(&dwarf, &unit, file.path_name());
+		let line = row.line().map_or(0, |line| line.get());
+		let col = match row.column() {
+			gimli::ColumnType::LeftEdge => 0,
+			gimli::ColumnType::Column(col) => col.get(),
+		};
+
+		let entry = (dir, name, line, col);
+		if !found.contains(&entry) {
+			found.push(entry);
+		}
+	}
+
+	// Bare file names live in the compilation directory, index 0.
+	assert_eq!(
+		found,
+		[
+			(String::new(), "main.x".to_owned(), 1, 1),
+			("lib".to_owned(), "util.x".to_owned(), 7, 3),
+			(String::new(), "main.x".to_owned(), 2, 1),
+		]
+	);
+}
+
+/// The names of the global variables described in the debug info, and
+/// whether each has a location.
+fn described_globals(bytes: &[u8]) -> Vec<(String, bool)> {
+	let dwarf = load_dwarf(bytes);
+	let dwarf = dwarf.borrow(|section| {
+		gimli::EndianSlice::new(section, gimli::LittleEndian)
+	});
+
+	let header = dwarf.units().next().unwrap().unwrap();
+	let unit = dwarf.unit(header).unwrap();
+	let mut entries = unit.entries();
+	let mut depth = 0;
+	let mut globals = Vec::new();
+
+	while let Some((delta, entry)) = entries.next_dfs().unwrap() {
+		depth += delta;
+		if depth != 1 || entry.tag() != gimli::DW_TAG_variable {
+			continue;
+		}
+
+		let name = entry.attr_value(gimli::DW_AT_name).unwrap().unwrap();
+		let has_location =
+			entry.attr_value(gimli::DW_AT_location).unwrap().is_some();
+		globals.push((read_str(&dwarf, &unit, name), has_location));
+	}
+
+	globals
+}
+
+#[test]
+fn globals_declared_while_vars_are_enabled() {
+	let mut compiler = compiler("debug_vars");
+
+	compiler.set_debug_vars(true);
+	compiler.create_var("traced", types::I64, None).unwrap();
+	compiler.set_debug_vars(false);
+	compiler.create_var("untraced", types::I64, None).unwrap();
+
+	let bytes = compiler.finish().unwrap();
+
+	assert_eq!(described_globals(&bytes), [("traced".to_owned(), true)]);
+}
+
+#[test]
+fn globals_declared_while_vars_are_disabled() {
+	let mut compiler = compiler("no_debug_vars");
+
+	compiler.create_var("untraced", types::I64, None).unwrap();
+	compiler.set_debug_vars(true);
+
+	let bytes = compiler.finish().unwrap();
+	let file = object::File::parse(&*bytes).unwrap();
+
+	assert!(file.section_by_name(".debug_info").is_none());
+}
+
+#[test]
+fn coff_debug_sections_use_section_relative_offsets() {
+	let mut compiler = CompilerBuilder::new()
+		.target("x86_64-pc-windows-msvc")
+		.build_object("debug_coff")
+		.unwrap();
+	let sig = FuncSig::new().ret(types::I32);
+
+	compiler
+		.compile_func("main", &sig, Linkage::Export, |c, f, _| {
+			let block = f.create_block();
+			f.switch_to_block(block);
+			f.seal_block(block);
+
+			c.set_location("main.x", 1, 1, f);
+			let x = f.ins().iconst(types::I32, 1);
+			f.ins().return_(&[x]);
+
+			Ok(())
+		})
+		.unwrap();
+
+	let bytes = compiler.finish().unwrap();
+	let file = object::File::parse(&*bytes).unwrap();
+	let debug_info = file.section_by_name(".debug_info").unwrap();
+
+	let section_relocs = debug_info
+		.relocations()
+		.filter_map(|(_, reloc)| match reloc.target() {
+			object::RelocationTarget::Symbol(index) => {
+				let symbol = file.symbol_by_index(index).unwrap();
+				(symbol.kind() == object::SymbolKind::Section)
+					.then(|| reloc.kind())
+			}
+			_ => None,
+		})
+		.collect::<Vec<_>>();
+
+	assert!(!section_relocs.is_empty());
+	assert!(section_relocs
+		.iter()
+		.all(|kind| *kind == RelocationKind::SectionOffset));
+}
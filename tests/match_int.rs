@@ -0,0 +1,116 @@
+#![cfg(feature = "jit")]
+
+use clif_builder_util::{CompileError, Compiler, FuncSig, MatchArm, Result};
+use cranelift::prelude::*;
+use cranelift_jit::JITModule;
+use cranelift_module::Linkage;
+use std::mem;
+
+/// Defines `name` as a function returning the index of the arm in `values`
+/// that matches its argument, or -1 if none does.
+fn define_match(
+	compiler: &mut Compiler<JITModule>,
+	name: &str,
+	values: &'static [u128],
+) -> Result<()> {
+	let sig = FuncSig::new().param(types::I32).ret(types::I32);
+
+	compiler.compile_func(name, &sig, Linkage::Export, |c, f, _| {
+		let block = f.create_block();
+		f.append_block_params_for_function_params(block);
+		f.switch_to_block(block);
+		f.seal_block(block);
+		let x = f.block_params(block)[0];
+
+		let arms = values
+			.iter()
+			.enumerate()
+			.map(|(i, value)| {
+				let arm: MatchArm<JITModule> = Box::new(move |_, f| {
+					Ok(vec![f.ins().iconst(types::I32, i as i64)])
+				});
+				(*value, arm)
+			})
+			.collect();
+
+		let results = c.match_int(x, &[types::I32], f, arms, |_, f| {
+			Ok(vec![f.ins().iconst(types::I32, -1)])
+		})?;
+		f.ins().return_(&results);
+
+		Ok(())
+	})?;
+
+	Ok(())
+}
+
+fn get_match(
+	compiler: &Compiler<JITModule>,
+	name: &str,
+) -> extern "C" fn(i32) -> i32 {
+	let ptr = compiler.get_func(name).unwrap();
+	unsafe { mem::transmute(ptr) }
+}
+
+#[test]
+fn dense_arms_use_jump_table() {
+	let mut compiler = Compiler::jit().unwrap();
+	compiler.set_dump(true);
+	define_match(&mut compiler, "dense", &[0, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+	compiler.finalize().unwrap();
+
+	assert!(compiler.dump("dense").unwrap().clif.contains("br_table"));
+
+	let dense = get_match(&compiler, "dense");
+	for i in 0..8 {
+		assert_eq!(dense(i), i);
+	}
+	assert_eq!(dense(8), -1);
+	assert_eq!(dense(-1), -1);
+}
+
+#[test]
+fn sparse_arms_use_search_tree() {
+	let values = &[3, 100, 1000, 50_000, 1_000_000];
+
+	let mut compiler = Compiler::jit().unwrap();
+	compiler.set_dump(true);
+	define_match(&mut compiler, "sparse", values).unwrap();
+	compiler.finalize().unwrap();
+
+	assert!(!compiler.dump("sparse").unwrap().clif.contains("br_table"));
+
+	let sparse = get_match(&compiler, "sparse");
+	for (i, value) in values.iter().enumerate() {
+		assert_eq!(sparse(*value as i32), i as i32);
+	}
+	for miss in [0, 4, 99, 101, 999_999, -3] {
+		assert_eq!(sparse(miss), -1);
+	}
+}
+
+#[test]
+fn non_integer_scrutinees_are_rejected() {
+	let mut compiler = Compiler::jit().unwrap();
+	let sig = FuncSig::new().param(types::F64).ret(types::I32);
+
+	let err = compiler
+		.compile_func("float", &sig, Linkage::Export, |c, f, _| {
+			let block = f.create_block();
+			f.append_block_params_for_function_params(block);
+			f.switch_to_block(block);
+			f.seal_block(block);
+			let x = f.block_params(block)[0];
+
+			let results =
+				c.match_int(x, &[types::I32], f, Vec::new(), |_, f| {
+					Ok(vec![f.ins().iconst(types::I32, -1)])
+				})?;
+			f.ins().return_(&results);
+
+			Ok(())
+		})
+		.unwrap_err();
+
+	assert!(matches!(err, CompileError::InvalidScrutinee(types::F64)));
+}